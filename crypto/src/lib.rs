@@ -4,6 +4,7 @@ use argon2::{
 };
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine as _};
 use rand::Rng;
+use sha2::{Digest, Sha256};
 
 pub fn hash_password(password: &[u8]) -> String {
     let argon2 = argon2::Argon2::default();
@@ -28,5 +29,10 @@ pub fn verify_password(
 pub fn generate_token() -> String {
     let mut bytes = [0u8; 64];
     rand::thread_rng().fill(&mut bytes);
-    BASE64_URL_SAFE_NO_PAD.encode(&bytes)
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hex encoded SHA-256 of `data`
+pub fn checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
 }
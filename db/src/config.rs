@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+/// How `chatter` connects to SurrealDB, read from the `database` table of Rocket's config
+///
+/// ```toml
+/// [default.database]
+/// address = "wss://db.example.com"
+/// namespace = "production"
+/// database = "chatter"
+/// auth = "database"
+/// username = "chatter"
+/// password = "..."
+/// ```
+#[derive(serde::Deserialize)]
+pub struct Config {
+    /// `ws://` or `wss://` for a remote server, `mem://`, `rocksdb://<path>` or
+    /// `surrealkv://<path>` to run embedded
+    pub address: String,
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    #[serde(default = "default_database")]
+    pub database: String,
+    /// Which kind of user `username` and `password` belong to
+    #[serde(default)]
+    pub auth: AuthLevel,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// A file containing a Json Web Token, used instead of `username` and `password`
+    pub token_path: Option<PathBuf>,
+    /// Apply pending migrations on startup, otherwise they have to be applied
+    /// with `chatter_web migrate` before the server will start
+    #[serde(default = "default_auto_migrate")]
+    pub auto_migrate: bool,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthLevel {
+    #[default]
+    Root,
+    Database,
+}
+
+fn default_namespace() -> String {
+    "chatter".to_string()
+}
+
+fn default_database() -> String {
+    "chatter".to_string()
+}
+
+fn default_auto_migrate() -> bool {
+    true
+}
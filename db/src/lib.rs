@@ -1,9 +1,15 @@
-use rocket::fairing::Fairing;
-use surrealdb::{
-    engine::remote::ws::{Client, Ws, Wss},
-    opt::auth::{Database, Jwt, Root},
-    RecordId, Surreal,
-};
+mod config;
+mod memory;
+pub mod migration;
+mod surreal;
+#[cfg(test)]
+mod tests;
+
+pub use config::{AuthLevel, Config};
+pub use memory::MemoryStore;
+pub use migration::MigrationError;
+pub use surreal::DBConnection;
+pub use surrealdb::{Error, RecordId};
 
 #[derive(serde::Serialize)]
 pub struct CreateUser {
@@ -11,11 +17,13 @@ pub struct CreateUser {
     pub password: String,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 pub struct User {
-    pub id: surrealdb::RecordId,
+    pub id: RecordId,
     pub email: String,
     pub password: String,
+    /// Key of the profile picture, its variants are stored by the web server
+    pub avatar: Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -24,7 +32,7 @@ pub struct CreateSession {
     pub created: i64,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 pub struct Session {
     pub id: RecordId,
     pub user: RecordId,
@@ -37,15 +45,84 @@ pub struct CreateGroup {
     pub name: String,
     pub members: Vec<RecordId>,
     pub created: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 pub struct Group {
     pub id: RecordId,
     pub owner: RecordId,
     pub name: String,
     pub members: Vec<RecordId>,
     pub created: i64,
+    /// Pinned messages, in the order they were pinned
+    #[serde(default)]
+    pub pinned: Vec<RecordId>,
+    /// Members with the [`Role::Admin`] role
+    #[serde(default)]
+    pub admins: Vec<RecordId>,
+    /// Members with the [`Role::ReadOnly`] role
+    #[serde(default)]
+    pub read_only: Vec<RecordId>,
+    /// Set on direct messages to the [`Group::direct_key`] of its two users
+    #[serde(default)]
+    pub direct: Option<String>,
+    /// When the group was deleted, it can be restored until it's purged
+    #[serde(default)]
+    pub deleted: Option<i64>,
+    pub description: Option<String>,
+    pub topic: Option<String>,
+    /// Key of the group's icon, its variants are stored by the web server
+    pub icon: Option<String>,
+}
+
+/// Everything about a group its members can edit as text
+#[derive(serde::Serialize)]
+pub struct GroupSettings {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+}
+
+impl Group {
+    /// Identifies the direct messages between `a` and `b`, in either order
+    pub fn direct_key(a: &RecordId, b: &RecordId) -> String {
+        let (a, b) = (a.to_string(), b.to_string());
+        if a <= b {
+            format!("{a} {b}")
+        } else {
+            format!("{b} {a}")
+        }
+    }
+
+    /// The role of `user` in the group, `None` when they aren't a member
+    pub fn role_of(&self, user: &RecordId) -> Option<Role> {
+        if !self.members.contains(user) {
+            None
+        } else if &self.owner == user {
+            Some(Role::Owner)
+        } else if self.admins.contains(user) {
+            Some(Role::Admin)
+        } else if self.read_only.contains(user) {
+            Some(Role::ReadOnly)
+        } else {
+            Some(Role::Member)
+        }
+    }
+}
+
+/// What a member is allowed to do in a group, ordered from least to most privileged
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    ReadOnly,
+    Member,
+    Admin,
+    /// Every group has exactly one owner
+    Owner,
 }
 
 #[derive(serde::Serialize)]
@@ -54,165 +131,598 @@ pub struct CreateMessage {
     pub author: RecordId,
     pub text: String,
     pub created: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<RecordId>,
+    pub mentions: Vec<RecordId>,
+    pub system: bool,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 pub struct Message {
     pub id: RecordId,
     pub group: RecordId,
     pub author: RecordId,
+    /// Empty once the message has been deleted
     pub text: String,
     pub created: i64,
+    pub edited: Option<i64>,
+    /// Set when the message has been deleted, the message is kept as a tombstone
+    pub deleted: Option<i64>,
+    /// Previous texts of the message, oldest first
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+    /// The top level message of the thread this message replies to
+    pub reply_to: Option<RecordId>,
+    /// Members mentioned with `@username`
+    #[serde(default)]
+    pub mentions: Vec<RecordId>,
+    /// Written by the server to record a change to the group, `author` made the change
+    #[serde(default)]
+    pub system: bool,
 }
 
-pub struct DBConnection {
-    surreal: Surreal<Client>,
+#[derive(Clone, serde::Deserialize)]
+pub struct Revision {
+    pub text: String,
+    /// When this text was replaced by an edit
+    pub replaced: i64,
 }
 
-impl DBConnection {
-    pub async fn new(addr: String) -> Result<Self, surrealdb::Error> {
-        let db = Surreal::new::<Ws>(addr).await?;
-        // FIXME: username and password fields
-        db.signin(Root {
-            username: "root",
-            password: "root",
-        })
-        .await?;
-        db.use_ns("testing").use_db("chatter").await?;
+#[derive(serde::Deserialize)]
+pub struct ReplyCount {
+    pub reply_to: RecordId,
+    pub count: u64,
+}
 
-        Ok(Self { surreal: db })
+#[derive(serde::Serialize)]
+pub struct CreateReaction {
+    pub message: RecordId,
+    pub user: RecordId,
+    pub emoji: String,
+    pub created: i64,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct Reaction {
+    pub id: RecordId,
+    pub message: RecordId,
+    pub user: RecordId,
+    pub emoji: String,
+    pub created: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReactionCount {
+    pub message: RecordId,
+    pub emoji: String,
+    pub count: u64,
+    /// Whether the user the reactions were counted for is one of them
+    pub mine: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct CreateMention {
+    pub user: RecordId,
+    pub message: RecordId,
+    pub group: RecordId,
+    pub created: i64,
+}
+
+/// An entry in a user's mention inbox
+#[derive(Clone, serde::Deserialize)]
+pub struct Mention {
+    pub id: RecordId,
+    /// The mentioned user
+    pub user: RecordId,
+    pub message: RecordId,
+    pub group: RecordId,
+    pub created: i64,
+    /// When the user marked the mention as read
+    pub read: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct CreateAttachment {
+    pub group: RecordId,
+    pub uploader: RecordId,
+    pub name: String,
+    pub size: u64,
+    pub mime: String,
+    /// Hex encoded SHA-256 of the content
+    pub checksum: String,
+    pub created: i64,
+}
+
+/// Metadata of an uploaded file, the content is kept by the web server's storage
+#[derive(Clone, serde::Deserialize)]
+pub struct Attachment {
+    pub id: RecordId,
+    pub group: RecordId,
+    pub uploader: RecordId,
+    /// The message the file was sent with, `None` until it has been sent
+    pub message: Option<RecordId>,
+    pub name: String,
+    pub size: u64,
+    pub mime: String,
+    pub checksum: String,
+    pub created: i64,
+}
+
+/// What [`ChatStore::purge_groups`] removed which has blobs in the web server's storage
+#[derive(Default, serde::Deserialize)]
+pub struct PurgedGroups {
+    pub attachments: Vec<Attachment>,
+    /// Keys of the icons of the removed groups
+    pub icons: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct CreateReadMarker {
+    pub group: RecordId,
+    pub user: RecordId,
+    pub message: RecordId,
+    pub message_created: i64,
+    pub read: i64,
+}
+
+/// How far a member has read the messages of a group
+#[derive(Clone, serde::Deserialize)]
+pub struct ReadMarker {
+    pub id: RecordId,
+    pub group: RecordId,
+    pub user: RecordId,
+    /// The newest message the user has read
+    pub message: RecordId,
+    /// When `message` was created, messages after it are unread
+    pub message_created: i64,
+    /// When the marker was last moved
+    pub read: i64,
+}
+
+impl ReadMarker {
+    /// Whether `message` is at or before the marker
+    pub fn has_read(&self, message: &Message) -> bool {
+        (message.created, &message.id) <= (self.message_created, &self.message)
     }
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnreadCount {
+    pub group: RecordId,
+    pub count: u64,
+}
 
-    /// Prepares the database for usage with `chatter`
-    pub async fn prepare(&self) {
-        self.surreal
-            .query("DEFINE TABLE user")
-            .query("DEFINE TABLE token")
-            .query("DEFINE TABLE group")
-            .query("DEFINE TABLE message")
-            .await
-            .expect("Failed to prepare database");
+#[derive(serde::Serialize)]
+pub struct CreateInvite {
+    pub code: String,
+    pub group: RecordId,
+    pub creator: RecordId,
+    pub created: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u64>,
+}
+
+/// Lets anyone who knows its code join a group
+#[derive(Clone, serde::Deserialize)]
+pub struct Invite {
+    pub id: RecordId,
+    pub code: String,
+    pub group: RecordId,
+    pub creator: RecordId,
+    pub created: i64,
+    /// The invite can't be used from this time on
+    pub expires: Option<i64>,
+    /// How often the invite can be used, `None` for no limit
+    pub max_uses: Option<u64>,
+    pub uses: u64,
+}
+
+impl Invite {
+    /// Whether the invite can still be used at `now`
+    pub fn is_usable(&self, now: i64) -> bool {
+        self.expires.is_none_or(|expires| expires > now)
+            && self.max_uses.is_none_or(|max_uses| self.uses < max_uses)
     }
+}
+
+/// A full-text search through the messages of the groups `member` belongs to
+pub struct Search {
+    /// Words which all have to appear in a message
+    pub query: String,
+    pub member: RecordId,
+    /// Only search this group
+    pub group: Option<RecordId>,
+    pub author: Option<RecordId>,
+    /// Only messages created at or after this time
+    pub since: Option<i64>,
+    /// Only messages created before this time
+    pub until: Option<i64>,
+}
 
-    pub async fn get_user(&self, id: &str) -> Result<Option<User>, surrealdb::Error> {
-        self.surreal.select(("user", id)).await
+/// A message matching a [`Search`]
+#[derive(Clone, serde::Deserialize)]
+pub struct SearchHit {
+    pub message: Message,
+    /// Where the words of the query appear in the text
+    pub highlights: Vec<Highlight>,
+}
+
+/// A matched word in a message's text, as a range of characters
+#[derive(Clone, serde::Deserialize)]
+pub struct Highlight {
+    #[serde(rename = "s")]
+    pub start: usize,
+    #[serde(rename = "e")]
+    pub end: usize,
+}
+
+/// A position in a group's message history
+///
+/// Messages are ordered by `created` with the id breaking ties, so a cursor
+/// stays stable while new messages arrive.
+#[derive(Clone)]
+pub struct Cursor {
+    pub created: i64,
+    pub id: RecordId,
+}
+
+impl From<&Message> for Cursor {
+    fn from(message: &Message) -> Self {
+        Self {
+            created: message.created,
+            id: message.id.clone(),
+        }
     }
+}
 
-    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, surrealdb::Error> {
-        let mut res = self
-            .surreal
-            .query(format!("SELECT * FROM user WHERE email = \"{email}\""))
-            .await?;
+impl From<&SearchHit> for Cursor {
+    fn from(hit: &SearchHit) -> Self {
+        Self::from(&hit.message)
+    }
+}
 
-        res.take(0)
+impl From<&Mention> for Cursor {
+    fn from(mention: &Mention) -> Self {
+        Self {
+            created: mention.created,
+            id: mention.id.clone(),
+        }
     }
+}
+
+/// Which part of a group's message history to load
+#[derive(Clone)]
+pub enum Page {
+    /// The newest messages
+    Latest,
+    /// Messages older than the cursor
+    Before(Cursor),
+    /// Messages newer than the cursor
+    After(Cursor),
+}
+
+/// Shared handle to whichever [`ChatStore`] the server was started with
+pub type Store = std::sync::Arc<dyn ChatStore>;
+
+/// Every storage operation `chatter` relies on
+///
+/// [`DBConnection`] implements this on top of SurrealDB while [`MemoryStore`]
+/// keeps everything in process, which lets the routes run without a database.
+#[rocket::async_trait]
+pub trait ChatStore: Send + Sync {
+    async fn get_user(&self, id: &str) -> Result<Option<User>, surrealdb::Error>;
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, surrealdb::Error>;
 
-    pub async fn create_user(
+    async fn create_user(
         &self,
         id: &str, // Username
         user: CreateUser,
-    ) -> Result<Option<User>, surrealdb::Error> {
-        self.surreal.create(("user", id)).content(user).await
-    }
+    ) -> Result<Option<User>, surrealdb::Error>;
 
-    pub async fn get_session(&self, id: &str) -> Result<Option<Session>, surrealdb::Error> {
-        self.surreal.select(("session", id)).await
-    }
+    /// Returns the users with the given ids, skipping ones that don't exist
+    async fn get_users(&self, ids: Vec<RecordId>) -> Result<Vec<User>, surrealdb::Error>;
+
+    /// Replaces the profile picture of a user, `None` removes it
+    async fn set_avatar(
+        &self,
+        user: RecordId,
+        avatar: Option<String>,
+    ) -> Result<Option<User>, surrealdb::Error>;
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, surrealdb::Error>;
 
-    pub async fn create_session(
+    async fn create_session(
         &self,
         id: &str,
         session: CreateSession,
-    ) -> Result<Option<Session>, surrealdb::Error> {
-        self.surreal.create(("session", id)).content(session).await
-    }
+    ) -> Result<Option<Session>, surrealdb::Error>;
 
-    pub async fn remove_session(&self, id: RecordId) -> Result<Option<Session>, surrealdb::Error> {
-        self.surreal.delete(id).await
-    }
+    async fn remove_session(&self, id: RecordId) -> Result<Option<Session>, surrealdb::Error>;
 
-    pub async fn get_messages(
+    /// Returns up to `count` top level messages of `group` from `page`, newest first
+    async fn get_messages(
         &self,
         group: &RecordId,
+        page: Page,
         count: u64,
-        offset: u64,
-    ) -> Result<Vec<Message>, surrealdb::Error> {
-        let mut res = self
-            .surreal
-            .query(format!(
-                "SELECT * FROM message WHERE group = {group} ORDER created DESC START {offset} LIMIT {count}"
-            ))
-            .await?;
-
-        res.take(0)
-    }
+    ) -> Result<Vec<Message>, surrealdb::Error>;
 
-    pub async fn create_message(
+    /// Returns up to `count` replies to `parent` from `page`, newest first
+    async fn get_thread(
+        &self,
+        parent: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error>;
+
+    /// Counts the replies of each of `parents`, parents without replies are left out
+    async fn count_replies(
+        &self,
+        parents: Vec<RecordId>,
+    ) -> Result<Vec<ReplyCount>, surrealdb::Error>;
+
+    /// Returns up to `count` messages of `group`, replies included, which come
+    /// after `since`, oldest first
+    async fn get_messages_since(
+        &self,
+        group: &RecordId,
+        since: Cursor,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error>;
+
+    async fn create_message(
         &self,
         message: CreateMessage,
-    ) -> Result<Option<Message>, surrealdb::Error> {
-        self.surreal.create("message").content(message).await
-    }
+    ) -> Result<Option<Message>, surrealdb::Error>;
 
-    pub async fn get_group(&self, id: &str) -> Result<Option<Group>, surrealdb::Error> {
-        self.surreal.select(("group", id)).await
-    }
+    async fn get_message(&self, id: &str) -> Result<Option<Message>, surrealdb::Error>;
+
+    /// Returns the messages with the given ids, skipping ones that don't exist
+    async fn get_messages_by_ids(
+        &self,
+        ids: Vec<RecordId>,
+    ) -> Result<Vec<Message>, surrealdb::Error>;
 
-    pub async fn get_groups_by_member(
+    /// Returns up to `count` messages matching `search` from `page`, newest first
+    async fn search_messages(
+        &self,
+        search: Search,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<SearchHit>, surrealdb::Error>;
+
+    /// Replaces the text and mentions of a message, keeping the previous text
+    /// as a revision
+    ///
+    /// Members who aren't mentioned anymore lose the message from their inbox,
+    /// newly mentioned ones other than the author get it added.
+    ///
+    /// Returns `None` when the message doesn't exist or has been deleted.
+    async fn edit_message(
+        &self,
+        id: RecordId,
+        text: String,
+        mentions: Vec<RecordId>,
+        edited: i64,
+    ) -> Result<Option<Message>, surrealdb::Error>;
+
+    /// Turns a message into a tombstone, removing its text, revisions, reactions,
+    /// mentions and attachments, and unpins it
+    async fn delete_message(
+        &self,
+        id: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Message>, surrealdb::Error>;
+
+    /// Adds a reaction, returns `None` when the user already reacted with that emoji
+    async fn add_reaction(
+        &self,
+        reaction: CreateReaction,
+    ) -> Result<Option<Reaction>, surrealdb::Error>;
+
+    /// Removes a reaction, returns `None` when there was nothing to remove
+    async fn remove_reaction(
+        &self,
+        message: RecordId,
+        user: RecordId,
+        emoji: String,
+    ) -> Result<Option<Reaction>, surrealdb::Error>;
+
+    /// Counts the reactions on each of `messages` per emoji, as seen by `user`
+    async fn count_reactions(
+        &self,
+        messages: Vec<RecordId>,
+        user: &RecordId,
+    ) -> Result<Vec<ReactionCount>, surrealdb::Error>;
+
+    /// Moves the read marker of `marker.user` in `marker.group` forward to
+    /// `marker.message`, returning the marker as it is afterwards
+    ///
+    /// Markers never move back, reading older messages leaves them where they are.
+    async fn mark_read(
+        &self,
+        marker: CreateReadMarker,
+    ) -> Result<Option<ReadMarker>, surrealdb::Error>;
+
+    async fn get_read_markers(&self, group: &RecordId)
+        -> Result<Vec<ReadMarker>, surrealdb::Error>;
+
+    /// Counts the messages by others in each of `groups` which `user` hasn't
+    /// read, groups without unread messages are left out
+    async fn count_unread(
+        &self,
+        user: &RecordId,
+        groups: Vec<RecordId>,
+    ) -> Result<Vec<UnreadCount>, surrealdb::Error>;
+
+    async fn create_mentions(
+        &self,
+        mentions: Vec<CreateMention>,
+    ) -> Result<Vec<Mention>, surrealdb::Error>;
+
+    async fn get_mention(&self, id: &str) -> Result<Option<Mention>, surrealdb::Error>;
+
+    /// Returns up to `count` unread mentions of `user` from `page`, newest first
+    async fn get_unread_mentions(
+        &self,
+        user: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Mention>, surrealdb::Error>;
+
+    async fn read_mention(
+        &self,
+        id: RecordId,
+        read: i64,
+    ) -> Result<Option<Mention>, surrealdb::Error>;
+
+    async fn create_attachment(
+        &self,
+        attachment: CreateAttachment,
+    ) -> Result<Option<Attachment>, surrealdb::Error>;
+
+    async fn get_attachment(&self, id: &str) -> Result<Option<Attachment>, surrealdb::Error>;
+
+    /// Returns the attachments sent with any of `messages`, oldest first
+    async fn get_attachments(
+        &self,
+        messages: Vec<RecordId>,
+    ) -> Result<Vec<Attachment>, surrealdb::Error>;
+
+    /// Links attachments which haven't been sent yet to `message`
+    async fn attach_to_message(
+        &self,
+        ids: Vec<RecordId>,
+        message: RecordId,
+    ) -> Result<Vec<Attachment>, surrealdb::Error>;
+
+    async fn remove_attachment(&self, id: RecordId)
+        -> Result<Option<Attachment>, surrealdb::Error>;
+
+    /// Removes the attachments uploaded before `before` which were never sent
+    ///
+    /// Returns the removed attachments, their blobs are up to the caller.
+    async fn remove_unsent_attachments(
+        &self,
+        before: i64,
+    ) -> Result<Vec<Attachment>, surrealdb::Error>;
+
+    /// Returns the group with `id`, deleted groups are left out
+    async fn get_group(&self, id: &str) -> Result<Option<Group>, surrealdb::Error>;
+
+    async fn get_groups_by_member(
         &self,
         member: RecordId,
         offset: u64,
         count: u64,
-    ) -> Result<Vec<Group>, surrealdb::Error> {
-        let mut res = self
-            .surreal
-            .query(format!(
-                "SELECT * FROM group WHERE members contains {member} START {offset} LIMIT {count}"
-            ))
-            .await?;
-
-        res.take(0)
-    }
+    ) -> Result<Vec<Group>, surrealdb::Error>;
 
-    pub async fn create_group(
+    async fn create_group(&self, group: CreateGroup) -> Result<Option<Group>, surrealdb::Error>;
+
+    /// Returns the direct messages with `group.direct`, creating them from
+    /// `group` when they don't exist yet
+    async fn open_direct_group(
         &self,
         group: CreateGroup,
-    ) -> Result<Option<Group>, surrealdb::Error> {
-        self.surreal.create("group").content(group).await
-    }
+    ) -> Result<Option<Group>, surrealdb::Error>;
 
-    pub async fn add_member_to_group(
+    /// Adds `member` to `group`, adding someone who is a member already changes nothing
+    async fn add_member_to_group(
         &self,
         group: RecordId,
         member: RecordId,
-    ) -> Result<Option<Group>, surrealdb::Error> {
-        let mut res = self
-            .surreal
-            .query(format!("UPDATE {group} SET members += {member}"))
-            .await?;
+    ) -> Result<Option<Group>, surrealdb::Error>;
 
-        res.take(0)
-    }
+    /// Replaces the name, description and topic of `group`
+    async fn update_group(
+        &self,
+        group: RecordId,
+        settings: GroupSettings,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    /// Replaces the icon of a group, `None` removes it
+    async fn set_group_icon(
+        &self,
+        group: RecordId,
+        icon: Option<String>,
+    ) -> Result<Option<Group>, surrealdb::Error>;
 
-    pub async fn remove_member_from_group(
+    /// Gives `member` a new role in `group`
+    ///
+    /// Giving [`Role::Owner`] transfers the group, the previous owner becomes
+    /// an admin. Returns `None` when `member` isn't in the group or is its owner.
+    async fn set_role(
         &self,
         group: RecordId,
         member: RecordId,
-    ) -> Result<Option<Group>, surrealdb::Error> {
-        let mut res = self
-            .surreal
-            .query(format!("UPDATE {group} SET members -= {member}"))
-            .await?;
+        role: Role,
+    ) -> Result<Option<Group>, surrealdb::Error>;
 
-        res.take(0)
-    }
-}
+    /// Pins `message` in `group`
+    ///
+    /// Returns `None` when the message is already pinned or the group has
+    /// `limit` pinned messages.
+    async fn pin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+        limit: usize,
+    ) -> Result<Option<Group>, surrealdb::Error>;
 
-impl Fairing for DBConnection {
-    fn info(&self) -> rocket::fairing::Info {
-        rocket::fairing::Info {
-            name: "database_connection",
-            kind: rocket::fairing::Kind::Ignite,
-        }
-    }
+    async fn unpin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    async fn remove_member_from_group(
+        &self,
+        group: RecordId,
+        member: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    /// Marks `group` as deleted, it disappears but its messages are kept
+    async fn delete_group(
+        &self,
+        group: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    /// Brings back `group` if `owner` owns it and it was deleted at or after `since`
+    async fn restore_group(
+        &self,
+        group: RecordId,
+        owner: RecordId,
+        since: i64,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    /// Removes the groups deleted before `before` for good, together with
+    /// their messages and everything belonging to those
+    ///
+    /// Returns the removed attachments and group icons, their blobs are up to the caller.
+    async fn purge_groups(&self, before: i64) -> Result<PurgedGroups, surrealdb::Error>;
+
+    async fn get_invite(&self, code: &str) -> Result<Option<Invite>, surrealdb::Error>;
+
+    /// Returns every invite of `group`, newest first
+    async fn get_invites(&self, group: &RecordId) -> Result<Vec<Invite>, surrealdb::Error>;
+
+    async fn create_invite(&self, invite: CreateInvite)
+        -> Result<Option<Invite>, surrealdb::Error>;
+
+    /// Counts a use of `invite` and adds `member` to its group in one step
+    ///
+    /// Returns `None` when the invite doesn't exist anymore, has expired at
+    /// `now` or has been used up.
+    async fn use_invite(
+        &self,
+        invite: RecordId,
+        member: RecordId,
+        now: i64,
+    ) -> Result<Option<Group>, surrealdb::Error>;
+
+    async fn remove_invite(&self, id: RecordId) -> Result<Option<Invite>, surrealdb::Error>;
 }
@@ -0,0 +1,1095 @@
+use std::{
+    cmp::Ordering,
+    sync::{Mutex, MutexGuard},
+};
+
+use surrealdb::RecordId;
+
+use crate::{
+    Attachment, ChatStore, CreateAttachment, CreateGroup, CreateInvite, CreateMention,
+    CreateMessage, CreateReaction, CreateReadMarker, CreateSession, CreateUser, Cursor, Group,
+    GroupSettings, Highlight, Invite, Mention, Message, Page, PurgedGroups, Reaction,
+    ReactionCount, ReadMarker, ReplyCount, Revision, Role, Search, SearchHit, Session, UnreadCount,
+    User,
+};
+
+#[derive(Default)]
+struct Tables {
+    next_id: u64,
+    users: Vec<User>,
+    sessions: Vec<Session>,
+    groups: Vec<Group>,
+    messages: Vec<Message>,
+    reactions: Vec<Reaction>,
+    mentions: Vec<Mention>,
+    attachments: Vec<Attachment>,
+    read_markers: Vec<ReadMarker>,
+    invites: Vec<Invite>,
+}
+
+impl Tables {
+    /// Generates a new record id, the in-memory counterpart of SurrealDB's random ids
+    fn generate_id(&mut self, table: &str) -> RecordId {
+        self.next_id += 1;
+        RecordId::from((table, format!("{table}{}", self.next_id)))
+    }
+}
+
+/// A [`ChatStore`] which keeps every record in memory
+///
+/// Nothing is persisted, this is meant for tests and local development.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().expect("MemoryStore lock was poisoned")
+    }
+}
+
+/// Pages through `records`, newest first
+fn paginate<'a, T: Clone + 'a>(
+    records: impl Iterator<Item = &'a T>,
+    cursor: impl Fn(&T) -> Cursor,
+    page: Page,
+    count: u64,
+) -> Vec<T> {
+    let mut records: Vec<T> = records
+        .filter(|r| match &page {
+            Page::Latest => true,
+            Page::Before(before) => position(&cursor(r)) < position(before),
+            Page::After(after) => position(&cursor(r)) > position(after),
+        })
+        .cloned()
+        .collect();
+    records.sort_by(|a, b| {
+        position(&cursor(b))
+            .partial_cmp(&position(&cursor(a)))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let count = count as usize;
+    if matches!(page, Page::After(_)) {
+        // Keep the records closest to the cursor
+        let skip = records.len().saturating_sub(count);
+        records.drain(..skip);
+    } else {
+        records.truncate(count);
+    }
+
+    records
+}
+
+/// The ordering key of a record, see [`Cursor`]
+fn position(cursor: &Cursor) -> (i64, &RecordId) {
+    (cursor.created, &cursor.id)
+}
+
+/// Finds every word of `query` in `text`, ignoring case
+///
+/// A much simpler take on SurrealDB's full-text search: words match anywhere
+/// in the text, without stemming. Returns `None` unless every word is found.
+fn find_words(text: &str, query: &str) -> Option<Vec<Highlight>> {
+    let lowercase = |s: &str| -> Vec<char> {
+        s.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect()
+    };
+    let text = lowercase(text);
+
+    let mut highlights: Vec<Highlight> = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+
+        let word = lowercase(word);
+        let before = highlights.len();
+        for (start, window) in text.windows(word.len()).enumerate() {
+            if window == word.as_slice() {
+                highlights.push(Highlight {
+                    start,
+                    end: start + word.len(),
+                });
+            }
+        }
+        if highlights.len() == before {
+            return None;
+        }
+    }
+    highlights.sort_by_key(|h| h.start);
+
+    (!highlights.is_empty()).then_some(highlights)
+}
+
+fn already_exists(id: &RecordId) -> surrealdb::Error {
+    surrealdb::Error::Api(surrealdb::error::Api::Query(format!(
+        "Database record `{id}` already exists"
+    )))
+}
+
+#[rocket::async_trait]
+impl ChatStore for MemoryStore {
+    async fn get_user(&self, id: &str) -> Result<Option<User>, surrealdb::Error> {
+        let id = RecordId::from(("user", id));
+        Ok(self.tables().users.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, surrealdb::Error> {
+        Ok(self
+            .tables()
+            .users
+            .iter()
+            .find(|u| u.email == email)
+            .cloned())
+    }
+
+    async fn create_user(
+        &self,
+        id: &str, // Username
+        user: CreateUser,
+    ) -> Result<Option<User>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let id = RecordId::from(("user", id));
+        if tables.users.iter().any(|u| u.id == id) {
+            return Err(already_exists(&id));
+        }
+
+        let user = User {
+            id,
+            email: user.email,
+            password: user.password,
+            avatar: None,
+        };
+        tables.users.push(user.clone());
+        Ok(Some(user))
+    }
+
+    async fn get_users(&self, ids: Vec<RecordId>) -> Result<Vec<User>, surrealdb::Error> {
+        let tables = self.tables();
+        Ok(ids
+            .iter()
+            .filter_map(|id| tables.users.iter().find(|u| &u.id == id).cloned())
+            .collect())
+    }
+
+    async fn set_avatar(
+        &self,
+        user: RecordId,
+        avatar: Option<String>,
+    ) -> Result<Option<User>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(user) = tables.users.iter_mut().find(|u| u.id == user) else {
+            return Ok(None);
+        };
+
+        user.avatar = avatar;
+        Ok(Some(user.clone()))
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, surrealdb::Error> {
+        let id = RecordId::from(("session", id));
+        Ok(self.tables().sessions.iter().find(|s| s.id == id).cloned())
+    }
+
+    async fn create_session(
+        &self,
+        id: &str,
+        session: CreateSession,
+    ) -> Result<Option<Session>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let id = RecordId::from(("session", id));
+        if tables.sessions.iter().any(|s| s.id == id) {
+            return Err(already_exists(&id));
+        }
+
+        let session = Session {
+            id,
+            user: session.user,
+            created: session.created,
+        };
+        tables.sessions.push(session.clone());
+        Ok(Some(session))
+    }
+
+    async fn remove_session(&self, id: RecordId) -> Result<Option<Session>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let index = tables.sessions.iter().position(|s| s.id == id);
+        Ok(index.map(|index| tables.sessions.remove(index)))
+    }
+
+    async fn get_messages(
+        &self,
+        group: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let tables = self.tables();
+        let messages = tables
+            .messages
+            .iter()
+            .filter(|m| &m.group == group && m.reply_to.is_none());
+        Ok(paginate(messages, |m| Cursor::from(m), page, count))
+    }
+
+    async fn get_thread(
+        &self,
+        parent: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let tables = self.tables();
+        let messages = tables
+            .messages
+            .iter()
+            .filter(|m| m.reply_to.as_ref() == Some(parent));
+        Ok(paginate(messages, |m| Cursor::from(m), page, count))
+    }
+
+    async fn count_replies(
+        &self,
+        parents: Vec<RecordId>,
+    ) -> Result<Vec<ReplyCount>, surrealdb::Error> {
+        let tables = self.tables();
+        let mut counts: Vec<ReplyCount> = Vec::new();
+        for reply_to in tables.messages.iter().filter_map(|m| m.reply_to.as_ref()) {
+            if !parents.contains(reply_to) {
+                continue;
+            }
+
+            match counts.iter_mut().find(|c| &c.reply_to == reply_to) {
+                Some(count) => count.count += 1,
+                None => counts.push(ReplyCount {
+                    reply_to: reply_to.clone(),
+                    count: 1,
+                }),
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn get_messages_since(
+        &self,
+        group: &RecordId,
+        since: Cursor,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let tables = self.tables();
+        let mut messages = paginate(
+            tables.messages.iter().filter(|m| &m.group == group),
+            |m| Cursor::from(m),
+            Page::After(since),
+            count,
+        );
+        messages.reverse();
+
+        Ok(messages)
+    }
+
+    async fn create_message(
+        &self,
+        message: CreateMessage,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let message = Message {
+            id: tables.generate_id("message"),
+            group: message.group,
+            author: message.author,
+            text: message.text,
+            created: message.created,
+            edited: None,
+            deleted: None,
+            revisions: Vec::new(),
+            reply_to: message.reply_to,
+            mentions: message.mentions,
+            system: message.system,
+        };
+        tables.messages.push(message.clone());
+        Ok(Some(message))
+    }
+
+    async fn get_message(&self, id: &str) -> Result<Option<Message>, surrealdb::Error> {
+        let id = RecordId::from(("message", id));
+        Ok(self.tables().messages.iter().find(|m| m.id == id).cloned())
+    }
+
+    async fn get_messages_by_ids(
+        &self,
+        ids: Vec<RecordId>,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let tables = self.tables();
+        Ok(ids
+            .iter()
+            .filter_map(|id| tables.messages.iter().find(|m| &m.id == id).cloned())
+            .collect())
+    }
+
+    async fn search_messages(
+        &self,
+        search: Search,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<SearchHit>, surrealdb::Error> {
+        let tables = self.tables();
+        let hits: Vec<SearchHit> = tables
+            .messages
+            .iter()
+            .filter(|m| {
+                tables.groups.iter().any(|g| {
+                    g.id == m.group && g.members.contains(&search.member) && g.deleted.is_none()
+                }) && search.group.as_ref().is_none_or(|group| &m.group == group)
+                    && search
+                        .author
+                        .as_ref()
+                        .is_none_or(|author| &m.author == author)
+                    && search.since.is_none_or(|since| m.created >= since)
+                    && search.until.is_none_or(|until| m.created < until)
+            })
+            .filter_map(|m| {
+                Some(SearchHit {
+                    highlights: find_words(&m.text, &search.query)?,
+                    message: m.clone(),
+                })
+            })
+            .collect();
+
+        Ok(paginate(hits.iter(), |h| Cursor::from(h), page, count))
+    }
+
+    async fn edit_message(
+        &self,
+        id: RecordId,
+        text: String,
+        mentions: Vec<RecordId>,
+        edited: i64,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(message) = tables
+            .messages
+            .iter_mut()
+            .find(|m| m.id == id && m.deleted.is_none())
+        else {
+            return Ok(None);
+        };
+
+        let previous = std::mem::replace(&mut message.text, text);
+        message.revisions.push(Revision {
+            text: previous,
+            replaced: edited,
+        });
+        message.mentions = mentions;
+        message.edited = Some(edited);
+        let message = message.clone();
+
+        tables
+            .mentions
+            .retain(|m| m.message != id || message.mentions.contains(&m.user));
+        for user in &message.mentions {
+            let notified = tables
+                .mentions
+                .iter()
+                .any(|m| m.message == id && &m.user == user);
+            if user != &message.author && !notified {
+                let mention = Mention {
+                    id: tables.generate_id("mention"),
+                    user: user.clone(),
+                    message: id.clone(),
+                    group: message.group.clone(),
+                    created: edited,
+                    read: None,
+                };
+                tables.mentions.push(mention);
+            }
+        }
+
+        Ok(Some(message))
+    }
+
+    async fn delete_message(
+        &self,
+        id: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        let mut tables = self.tables();
+        tables.reactions.retain(|r| r.message != id);
+        tables.mentions.retain(|m| m.message != id);
+        tables
+            .attachments
+            .retain(|a| a.message.as_ref() != Some(&id));
+        for group in tables.groups.iter_mut() {
+            group.pinned.retain(|m| m != &id);
+        }
+        let Some(message) = tables.messages.iter_mut().find(|m| m.id == id) else {
+            return Ok(None);
+        };
+
+        message.text.clear();
+        message.revisions.clear();
+        message.deleted = Some(deleted);
+        Ok(Some(message.clone()))
+    }
+
+    async fn add_reaction(
+        &self,
+        reaction: CreateReaction,
+    ) -> Result<Option<Reaction>, surrealdb::Error> {
+        let mut tables = self.tables();
+        if tables.reactions.iter().any(|r| {
+            r.message == reaction.message && r.user == reaction.user && r.emoji == reaction.emoji
+        }) {
+            return Ok(None);
+        }
+
+        let reaction = Reaction {
+            id: tables.generate_id("reaction"),
+            message: reaction.message,
+            user: reaction.user,
+            emoji: reaction.emoji,
+            created: reaction.created,
+        };
+        tables.reactions.push(reaction.clone());
+        Ok(Some(reaction))
+    }
+
+    async fn remove_reaction(
+        &self,
+        message: RecordId,
+        user: RecordId,
+        emoji: String,
+    ) -> Result<Option<Reaction>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(index) = tables
+            .reactions
+            .iter()
+            .position(|r| r.message == message && r.user == user && r.emoji == emoji)
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(tables.reactions.remove(index)))
+    }
+
+    async fn count_reactions(
+        &self,
+        messages: Vec<RecordId>,
+        user: &RecordId,
+    ) -> Result<Vec<ReactionCount>, surrealdb::Error> {
+        let tables = self.tables();
+        let mut counts: Vec<ReactionCount> = Vec::new();
+        for reaction in tables.reactions.iter() {
+            if !messages.contains(&reaction.message) {
+                continue;
+            }
+
+            let mine = &reaction.user == user;
+            match counts
+                .iter_mut()
+                .find(|c| c.message == reaction.message && c.emoji == reaction.emoji)
+            {
+                Some(count) => {
+                    count.count += 1;
+                    count.mine |= mine;
+                }
+                None => counts.push(ReactionCount {
+                    message: reaction.message.clone(),
+                    emoji: reaction.emoji.clone(),
+                    count: 1,
+                    mine,
+                }),
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn mark_read(
+        &self,
+        marker: CreateReadMarker,
+    ) -> Result<Option<ReadMarker>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let position = (marker.message_created, &marker.message);
+        if let Some(existing) = tables
+            .read_markers
+            .iter_mut()
+            .find(|m| m.group == marker.group && m.user == marker.user)
+        {
+            if (existing.message_created, &existing.message) < position {
+                existing.message = marker.message;
+                existing.message_created = marker.message_created;
+                existing.read = marker.read;
+            }
+            return Ok(Some(existing.clone()));
+        }
+
+        let marker = ReadMarker {
+            id: tables.generate_id("read_marker"),
+            group: marker.group,
+            user: marker.user,
+            message: marker.message,
+            message_created: marker.message_created,
+            read: marker.read,
+        };
+        tables.read_markers.push(marker.clone());
+        Ok(Some(marker))
+    }
+
+    async fn get_read_markers(
+        &self,
+        group: &RecordId,
+    ) -> Result<Vec<ReadMarker>, surrealdb::Error> {
+        Ok(self
+            .tables()
+            .read_markers
+            .iter()
+            .filter(|m| &m.group == group)
+            .cloned()
+            .collect())
+    }
+
+    async fn count_unread(
+        &self,
+        user: &RecordId,
+        groups: Vec<RecordId>,
+    ) -> Result<Vec<UnreadCount>, surrealdb::Error> {
+        let tables = self.tables();
+        let mut counts: Vec<UnreadCount> = Vec::new();
+        for message in tables.messages.iter() {
+            if !groups.contains(&message.group)
+                || &message.author == user
+                || message.deleted.is_some()
+            {
+                continue;
+            }
+
+            let read = tables
+                .read_markers
+                .iter()
+                .find(|m| m.group == message.group && &m.user == user)
+                .is_some_and(|marker| marker.has_read(message));
+            if read {
+                continue;
+            }
+
+            match counts.iter_mut().find(|c| c.group == message.group) {
+                Some(count) => count.count += 1,
+                None => counts.push(UnreadCount {
+                    group: message.group.clone(),
+                    count: 1,
+                }),
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn create_mentions(
+        &self,
+        mentions: Vec<CreateMention>,
+    ) -> Result<Vec<Mention>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let mut created = Vec::with_capacity(mentions.len());
+        for mention in mentions {
+            let mention = Mention {
+                id: tables.generate_id("mention"),
+                user: mention.user,
+                message: mention.message,
+                group: mention.group,
+                created: mention.created,
+                read: None,
+            };
+            tables.mentions.push(mention.clone());
+            created.push(mention);
+        }
+
+        Ok(created)
+    }
+
+    async fn get_mention(&self, id: &str) -> Result<Option<Mention>, surrealdb::Error> {
+        let id = RecordId::from(("mention", id));
+        Ok(self.tables().mentions.iter().find(|m| m.id == id).cloned())
+    }
+
+    async fn get_unread_mentions(
+        &self,
+        user: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Mention>, surrealdb::Error> {
+        let tables = self.tables();
+        let mentions = tables.mentions.iter().filter(|m| {
+            &m.user == user
+                && m.read.is_none()
+                && tables
+                    .groups
+                    .iter()
+                    .any(|g| g.id == m.group && g.deleted.is_none())
+        });
+        Ok(paginate(mentions, |m| Cursor::from(m), page, count))
+    }
+
+    async fn read_mention(
+        &self,
+        id: RecordId,
+        read: i64,
+    ) -> Result<Option<Mention>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(mention) = tables.mentions.iter_mut().find(|m| m.id == id) else {
+            return Ok(None);
+        };
+
+        mention.read = Some(read);
+        Ok(Some(mention.clone()))
+    }
+
+    async fn create_attachment(
+        &self,
+        attachment: CreateAttachment,
+    ) -> Result<Option<Attachment>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let attachment = Attachment {
+            id: tables.generate_id("attachment"),
+            group: attachment.group,
+            uploader: attachment.uploader,
+            message: None,
+            name: attachment.name,
+            size: attachment.size,
+            mime: attachment.mime,
+            checksum: attachment.checksum,
+            created: attachment.created,
+        };
+        tables.attachments.push(attachment.clone());
+        Ok(Some(attachment))
+    }
+
+    async fn get_attachment(&self, id: &str) -> Result<Option<Attachment>, surrealdb::Error> {
+        let id = RecordId::from(("attachment", id));
+        Ok(self
+            .tables()
+            .attachments
+            .iter()
+            .find(|a| a.id == id)
+            .cloned())
+    }
+
+    async fn get_attachments(
+        &self,
+        messages: Vec<RecordId>,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let tables = self.tables();
+        let mut attachments: Vec<Attachment> = tables
+            .attachments
+            .iter()
+            .filter(|a| a.message.as_ref().is_some_and(|m| messages.contains(m)))
+            .cloned()
+            .collect();
+        attachments.sort_by_key(|a| a.created);
+
+        Ok(attachments)
+    }
+
+    async fn attach_to_message(
+        &self,
+        ids: Vec<RecordId>,
+        message: RecordId,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let mut attached = Vec::new();
+        for attachment in tables.attachments.iter_mut() {
+            if ids.contains(&attachment.id) && attachment.message.is_none() {
+                attachment.message = Some(message.clone());
+                attached.push(attachment.clone());
+            }
+        }
+
+        Ok(attached)
+    }
+
+    async fn remove_attachment(
+        &self,
+        id: RecordId,
+    ) -> Result<Option<Attachment>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(index) = tables.attachments.iter().position(|a| a.id == id) else {
+            return Ok(None);
+        };
+
+        Ok(Some(tables.attachments.remove(index)))
+    }
+
+    async fn remove_unsent_attachments(
+        &self,
+        before: i64,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let (removed, kept) = std::mem::take(&mut tables.attachments)
+            .into_iter()
+            .partition(|a| a.message.is_none() && a.created < before);
+        tables.attachments = kept;
+
+        Ok(removed)
+    }
+
+    async fn get_group(&self, id: &str) -> Result<Option<Group>, surrealdb::Error> {
+        let id = RecordId::from(("group", id));
+        Ok(self
+            .tables()
+            .groups
+            .iter()
+            .find(|g| g.id == id && g.deleted.is_none())
+            .cloned())
+    }
+
+    async fn get_groups_by_member(
+        &self,
+        member: RecordId,
+        offset: u64,
+        count: u64,
+    ) -> Result<Vec<Group>, surrealdb::Error> {
+        Ok(self
+            .tables()
+            .groups
+            .iter()
+            .filter(|g| g.members.contains(&member) && g.deleted.is_none())
+            .skip(offset as usize)
+            .take(count as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn create_group(&self, group: CreateGroup) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let group = Group {
+            id: tables.generate_id("group"),
+            owner: group.owner,
+            name: group.name,
+            members: group.members,
+            created: group.created,
+            pinned: Vec::new(),
+            admins: Vec::new(),
+            read_only: Vec::new(),
+            direct: group.direct,
+            deleted: None,
+            description: None,
+            topic: None,
+            icon: None,
+        };
+        tables.groups.push(group.clone());
+        Ok(Some(group))
+    }
+
+    async fn open_direct_group(
+        &self,
+        group: CreateGroup,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let existing = self
+            .tables()
+            .groups
+            .iter()
+            .find(|g| g.direct.is_some() && g.direct == group.direct)
+            .cloned();
+
+        match existing {
+            Some(existing) => Ok(Some(existing)),
+            None => self.create_group(group).await,
+        }
+    }
+
+    async fn add_member_to_group(
+        &self,
+        group: RecordId,
+        member: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        if !group.members.contains(&member) {
+            group.members.push(member);
+        }
+        Ok(Some(group.clone()))
+    }
+
+    async fn update_group(
+        &self,
+        group: RecordId,
+        settings: GroupSettings,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        group.name = settings.name;
+        group.description = settings.description;
+        group.topic = settings.topic;
+        Ok(Some(group.clone()))
+    }
+
+    async fn set_group_icon(
+        &self,
+        group: RecordId,
+        icon: Option<String>,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        group.icon = icon;
+        Ok(Some(group.clone()))
+    }
+
+    async fn set_role(
+        &self,
+        group: RecordId,
+        member: RecordId,
+        role: Role,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        if !group.members.contains(&member) || group.owner == member {
+            return Ok(None);
+        }
+
+        group.admins.retain(|m| m != &member);
+        group.read_only.retain(|m| m != &member);
+        match role {
+            Role::ReadOnly => group.read_only.push(member),
+            Role::Member => (),
+            Role::Admin => group.admins.push(member),
+            Role::Owner => {
+                let previous = std::mem::replace(&mut group.owner, member);
+                group.admins.push(previous);
+            }
+        }
+
+        Ok(Some(group.clone()))
+    }
+
+    async fn pin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+        limit: usize,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        if group.pinned.contains(&message) || group.pinned.len() >= limit {
+            return Ok(None);
+        }
+
+        group.pinned.push(message);
+        Ok(Some(group.clone()))
+    }
+
+    async fn unpin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        group.pinned.retain(|m| m != &message);
+        Ok(Some(group.clone()))
+    }
+
+    async fn remove_member_from_group(
+        &self,
+        group: RecordId,
+        member: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        group.members.retain(|m| m != &member);
+        group.admins.retain(|m| m != &member);
+        group.read_only.retain(|m| m != &member);
+        Ok(Some(group.clone()))
+    }
+
+    async fn delete_group(
+        &self,
+        group: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables
+            .groups
+            .iter_mut()
+            .find(|g| g.id == group && g.deleted.is_none())
+        else {
+            return Ok(None);
+        };
+
+        group.deleted = Some(deleted);
+        Ok(Some(group.clone()))
+    }
+
+    async fn restore_group(
+        &self,
+        group: RecordId,
+        owner: RecordId,
+        since: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(group) = tables.groups.iter_mut().find(|g| {
+            g.id == group && g.owner == owner && g.deleted.is_some_and(|deleted| deleted >= since)
+        }) else {
+            return Ok(None);
+        };
+
+        group.deleted = None;
+        Ok(Some(group.clone()))
+    }
+
+    async fn purge_groups(&self, before: i64) -> Result<PurgedGroups, surrealdb::Error> {
+        let mut tables = self.tables();
+        let groups: Vec<RecordId> = tables
+            .groups
+            .iter()
+            .filter(|g| g.deleted.is_some_and(|deleted| deleted < before))
+            .map(|g| g.id.clone())
+            .collect();
+        let messages: Vec<RecordId> = tables
+            .messages
+            .iter()
+            .filter(|m| groups.contains(&m.group))
+            .map(|m| m.id.clone())
+            .collect();
+
+        tables.reactions.retain(|r| !messages.contains(&r.message));
+        tables.mentions.retain(|m| !groups.contains(&m.group));
+        let (attachments, kept) = std::mem::take(&mut tables.attachments)
+            .into_iter()
+            .partition(|a| groups.contains(&a.group));
+        tables.attachments = kept;
+        tables.messages.retain(|m| !groups.contains(&m.group));
+        tables.read_markers.retain(|m| !groups.contains(&m.group));
+        tables.invites.retain(|i| !groups.contains(&i.group));
+        let (purged, kept): (Vec<Group>, Vec<Group>) = std::mem::take(&mut tables.groups)
+            .into_iter()
+            .partition(|g| groups.contains(&g.id));
+        tables.groups = kept;
+
+        Ok(PurgedGroups {
+            attachments,
+            icons: purged.into_iter().filter_map(|g| g.icon).collect(),
+        })
+    }
+
+    async fn get_invite(&self, code: &str) -> Result<Option<Invite>, surrealdb::Error> {
+        Ok(self
+            .tables()
+            .invites
+            .iter()
+            .find(|i| i.code == code)
+            .cloned())
+    }
+
+    async fn get_invites(&self, group: &RecordId) -> Result<Vec<Invite>, surrealdb::Error> {
+        let mut invites: Vec<Invite> = self
+            .tables()
+            .invites
+            .iter()
+            .filter(|i| &i.group == group)
+            .cloned()
+            .collect();
+        invites.sort_by_key(|i| std::cmp::Reverse(i.created));
+        Ok(invites)
+    }
+
+    async fn create_invite(
+        &self,
+        invite: CreateInvite,
+    ) -> Result<Option<Invite>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let id = tables.generate_id("invite");
+        if tables.invites.iter().any(|i| i.code == invite.code) {
+            return Err(already_exists(&id));
+        }
+
+        let invite = Invite {
+            id,
+            code: invite.code,
+            group: invite.group,
+            creator: invite.creator,
+            created: invite.created,
+            expires: invite.expires,
+            max_uses: invite.max_uses,
+            uses: 0,
+        };
+        tables.invites.push(invite.clone());
+        Ok(Some(invite))
+    }
+
+    async fn use_invite(
+        &self,
+        invite: RecordId,
+        member: RecordId,
+        now: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let Some(invite) = tables
+            .invites
+            .iter_mut()
+            .find(|i| i.id == invite && i.is_usable(now))
+        else {
+            return Ok(None);
+        };
+
+        invite.uses += 1;
+        let group = invite.group.clone();
+        let Some(group) = tables.groups.iter_mut().find(|g| g.id == group) else {
+            return Ok(None);
+        };
+
+        if !group.members.contains(&member) {
+            group.members.push(member);
+        }
+        Ok(Some(group.clone()))
+    }
+
+    async fn remove_invite(&self, id: RecordId) -> Result<Option<Invite>, surrealdb::Error> {
+        let mut tables = self.tables();
+        let index = tables.invites.iter().position(|i| i.id == id);
+        Ok(index.map(|index| tables.invites.remove(index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Snapshot, TestStore};
+
+    #[rocket::async_trait]
+    impl TestStore for MemoryStore {
+        async fn snapshot(&self) -> Snapshot {
+            let tables = self.tables();
+            Snapshot::new(
+                &tables.users,
+                &tables.sessions,
+                &tables.groups,
+                &tables.messages,
+            )
+        }
+    }
+}
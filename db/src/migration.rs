@@ -0,0 +1,125 @@
+use std::fmt;
+
+/// A schema change, applied once and recorded in the `migration` table
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: &'static str,
+}
+
+/// Every migration this binary knows about, ordered by version
+///
+/// New migrations are appended here with the next version, applied
+/// migrations must never be changed.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        up: include_str!("../migrations/0001_initial_schema.surql"),
+    },
+    Migration {
+        version: 2,
+        name: "message_edits",
+        up: include_str!("../migrations/0002_message_edits.surql"),
+    },
+    Migration {
+        version: 3,
+        name: "threads",
+        up: include_str!("../migrations/0003_threads.surql"),
+    },
+    Migration {
+        version: 4,
+        name: "reactions",
+        up: include_str!("../migrations/0004_reactions.surql"),
+    },
+    Migration {
+        version: 5,
+        name: "mentions",
+        up: include_str!("../migrations/0005_mentions.surql"),
+    },
+    Migration {
+        version: 6,
+        name: "attachments",
+        up: include_str!("../migrations/0006_attachments.surql"),
+    },
+    Migration {
+        version: 7,
+        name: "avatars",
+        up: include_str!("../migrations/0007_avatars.surql"),
+    },
+    Migration {
+        version: 8,
+        name: "search",
+        up: include_str!("../migrations/0008_search.surql"),
+    },
+    Migration {
+        version: 9,
+        name: "read_markers",
+        up: include_str!("../migrations/0009_read_markers.surql"),
+    },
+    Migration {
+        version: 10,
+        name: "pins",
+        up: include_str!("../migrations/0010_pins.surql"),
+    },
+    Migration {
+        version: 11,
+        name: "roles",
+        up: include_str!("../migrations/0011_roles.surql"),
+    },
+    Migration {
+        version: 12,
+        name: "invites",
+        up: include_str!("../migrations/0012_invites.surql"),
+    },
+    Migration {
+        version: 13,
+        name: "direct_messages",
+        up: include_str!("../migrations/0013_direct_messages.surql"),
+    },
+    Migration {
+        version: 14,
+        name: "group_deletion",
+        up: include_str!("../migrations/0014_group_deletion.surql"),
+    },
+    Migration {
+        version: 15,
+        name: "group_settings",
+        up: include_str!("../migrations/0015_group_settings.surql"),
+    },
+];
+
+/// The schema version this binary expects the database to be at
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Database(surrealdb::Error),
+    /// The database has been migrated by a newer version of `chatter`
+    NewerVersion {
+        database: u32,
+        binary: u32,
+    },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Database(e) => write!(f, "{e}"),
+            MigrationError::NewerVersion { database, binary } => write!(
+                f,
+                "the database is at schema version {database} but this binary only knows up to version {binary}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<surrealdb::Error> for MigrationError {
+    fn from(e: surrealdb::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
@@ -0,0 +1,1058 @@
+use rocket::fairing::Fairing;
+use serde::de::DeserializeOwned;
+use surrealdb::{
+    engine::any::{self, Any},
+    opt::auth::{Database, Jwt, Root},
+    RecordId, Surreal,
+};
+
+use crate::{
+    config::{AuthLevel, Config},
+    migration::{self, Migration, MigrationError, MIGRATIONS},
+    Attachment, ChatStore, CreateAttachment, CreateGroup, CreateInvite, CreateMention,
+    CreateMessage, CreateReaction, CreateReadMarker, CreateSession, CreateUser, Cursor, Group,
+    GroupSettings, Invite, Mention, Message, Page, PurgedGroups, Reaction, ReactionCount,
+    ReadMarker, ReplyCount, Role, Search, SearchHit, Session, UnreadCount, User,
+};
+
+pub struct DBConnection {
+    surreal: Surreal<Any>,
+}
+
+impl DBConnection {
+    /// Connects to the database described by `config`
+    ///
+    /// `ws://` and `wss://` addresses connect to a remote SurrealDB server,
+    /// `mem://`, `rocksdb://<path>` and `surrealkv://<path>` run SurrealDB embedded
+    /// in-process, which requires the matching `kv-*` feature to be enabled.
+    pub async fn new(config: &Config) -> Result<Self, surrealdb::Error> {
+        let db = any::connect(config.address.as_str()).await?;
+        // Embedded engines don't have any users to sign in as
+        if is_remote(&config.address) {
+            if let Some(token_path) = &config.token_path {
+                let token = std::fs::read_to_string(token_path).map_err(|e| {
+                    surrealdb::Error::Api(surrealdb::error::Api::Query(format!(
+                        "couldn't read 'Json Web Token' from file at '{}': {e}",
+                        token_path.display()
+                    )))
+                })?;
+                db.authenticate(Jwt::from(token.trim())).await?;
+            } else if let (Some(username), Some(password)) = (&config.username, &config.password) {
+                match config.auth {
+                    AuthLevel::Root => {
+                        db.signin(Root { username, password }).await?;
+                    }
+                    AuthLevel::Database => {
+                        db.signin(Database {
+                            namespace: &config.namespace,
+                            database: &config.database,
+                            username,
+                            password,
+                        })
+                        .await?;
+                    }
+                }
+            }
+        }
+        db.use_ns(&config.namespace)
+            .use_db(&config.database)
+            .await?;
+
+        Ok(Self { surreal: db })
+    }
+
+    /// Returns the schema version of the database, `0` when nothing has been applied yet
+    pub async fn schema_version(&self) -> Result<u32, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("DEFINE TABLE IF NOT EXISTS migration SCHEMAFULL")
+            .query("DEFINE FIELD IF NOT EXISTS version ON migration TYPE int")
+            .query("DEFINE FIELD IF NOT EXISTS name ON migration TYPE string")
+            .query("DEFINE FIELD IF NOT EXISTS applied ON migration TYPE int")
+            .query("SELECT VALUE version FROM migration ORDER BY version DESC LIMIT 1")
+            .await?
+            .check()?;
+
+        let version: Option<u32> = res.take(4)?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// Returns the migrations that haven't been applied to the database yet
+    ///
+    /// Fails when the database is at a newer version than this binary knows about.
+    pub async fn pending_migrations(&self) -> Result<&'static [Migration], MigrationError> {
+        let version = self.schema_version().await?;
+        let latest = migration::latest_version();
+        if version > latest {
+            return Err(MigrationError::NewerVersion {
+                database: version,
+                binary: latest,
+            });
+        }
+
+        let applied = MIGRATIONS.partition_point(|migration| migration.version <= version);
+        Ok(&MIGRATIONS[applied..])
+    }
+
+    /// Applies every pending migration in order, returning how many were applied
+    ///
+    /// Each migration runs in its own transaction together with its `migration` record.
+    pub async fn migrate(&self) -> Result<usize, MigrationError> {
+        let pending = self.pending_migrations().await?;
+        for migration in pending {
+            self.apply(migration).await?;
+        }
+
+        Ok(pending.len())
+    }
+
+    /// Applies `migration` in a transaction together with its `migration` record
+    async fn apply(&self, migration: &Migration) -> Result<(), surrealdb::Error> {
+        self.surreal
+            .query("BEGIN TRANSACTION")
+            .query(migration.up)
+            .query(
+                "CREATE type::thing('migration', $version) CONTENT { version: $version, name: $name, applied: $applied }",
+            )
+            .query("COMMIT TRANSACTION")
+            .bind(("version", migration.version))
+            .bind(("name", migration.name))
+            .bind(("applied", chrono::Utc::now().timestamp_millis()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Pages through the records of `table` matching `filter`, newest first
+    ///
+    /// `filter` is static SurrealQL which can refer to `scope` as `$scope`.
+    async fn page_records<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        filter: &'static str,
+        scope: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<T>, surrealdb::Error> {
+        let ascending = matches!(page, Page::After(_));
+        let (condition, order, cursor) = page_condition(page);
+
+        let mut query = self
+            .surreal
+            .query(format!(
+                "SELECT * FROM {table} WHERE {filter} {condition} ORDER BY created {order}, id {order} LIMIT $count"
+            ))
+            .bind(("scope", scope.clone()))
+            .bind(("count", count));
+        if let Some(cursor) = cursor {
+            query = query
+                .bind(("created", cursor.created))
+                .bind(("id", cursor.id));
+        }
+
+        let mut records: Vec<T> = query.await?.take(0)?;
+        if ascending {
+            records.reverse();
+        }
+
+        Ok(records)
+    }
+}
+
+/// The condition selecting the records of `page`, the order to load them in
+/// and the cursor to bind as `$created` and `$id`
+fn page_condition(page: Page) -> (&'static str, &'static str, Option<Cursor>) {
+    match page {
+        Page::Latest => ("", "DESC", None),
+        Page::Before(cursor) => (
+            "AND (created < $created OR (created = $created AND id < $id))",
+            "DESC",
+            Some(cursor),
+        ),
+        Page::After(cursor) => (
+            "AND (created > $created OR (created = $created AND id > $id))",
+            "ASC",
+            Some(cursor),
+        ),
+    }
+}
+
+fn is_remote(addr: &str) -> bool {
+    ["ws://", "wss://", "http://", "https://"]
+        .iter()
+        .any(|scheme| addr.starts_with(scheme))
+}
+
+#[rocket::async_trait]
+impl ChatStore for DBConnection {
+    async fn get_user(&self, id: &str) -> Result<Option<User>, surrealdb::Error> {
+        self.surreal.select(("user", id)).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM user WHERE email = $email")
+            .bind(("email", email.to_string()))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn create_user(
+        &self,
+        id: &str, // Username
+        user: CreateUser,
+    ) -> Result<Option<User>, surrealdb::Error> {
+        self.surreal.create(("user", id)).content(user).await
+    }
+
+    async fn get_users(&self, ids: Vec<RecordId>) -> Result<Vec<User>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM $ids")
+            .bind(("ids", ids))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn set_avatar(
+        &self,
+        user: RecordId,
+        avatar: Option<String>,
+    ) -> Result<Option<User>, surrealdb::Error> {
+        // `option<string>` fields can't be set to NULL, they have to be unset
+        let query = match avatar {
+            Some(_) => "UPDATE $user SET avatar = $avatar",
+            None => "UPDATE $user UNSET avatar",
+        };
+
+        let mut res = self
+            .surreal
+            .query(query)
+            .bind(("user", user))
+            .bind(("avatar", avatar))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>, surrealdb::Error> {
+        self.surreal.select(("session", id)).await
+    }
+
+    async fn create_session(
+        &self,
+        id: &str,
+        session: CreateSession,
+    ) -> Result<Option<Session>, surrealdb::Error> {
+        self.surreal.create(("session", id)).content(session).await
+    }
+
+    async fn remove_session(&self, id: RecordId) -> Result<Option<Session>, surrealdb::Error> {
+        self.surreal.delete(id).await
+    }
+
+    async fn get_messages(
+        &self,
+        group: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        self.page_records(
+            "message",
+            "group = $scope AND reply_to = NONE",
+            group,
+            page,
+            count,
+        )
+        .await
+    }
+
+    async fn get_thread(
+        &self,
+        parent: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        self.page_records("message", "reply_to = $scope", parent, page, count)
+            .await
+    }
+
+    async fn count_replies(
+        &self,
+        parents: Vec<RecordId>,
+    ) -> Result<Vec<ReplyCount>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT reply_to, count() AS count FROM message WHERE reply_to IN $parents GROUP BY reply_to")
+            .bind(("parents", parents))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_messages_since(
+        &self,
+        group: &RecordId,
+        since: Cursor,
+        count: u64,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let mut messages: Vec<Message> = self
+            .page_records(
+                "message",
+                "group = $scope",
+                group,
+                Page::After(since),
+                count,
+            )
+            .await?;
+        messages.reverse();
+
+        Ok(messages)
+    }
+
+    async fn create_message(
+        &self,
+        message: CreateMessage,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        self.surreal.create("message").content(message).await
+    }
+
+    async fn get_message(&self, id: &str) -> Result<Option<Message>, surrealdb::Error> {
+        self.surreal.select(("message", id)).await
+    }
+
+    async fn get_messages_by_ids(
+        &self,
+        ids: Vec<RecordId>,
+    ) -> Result<Vec<Message>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM $ids")
+            .bind(("ids", ids))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn search_messages(
+        &self,
+        search: Search,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<SearchHit>, surrealdb::Error> {
+        let ascending = matches!(page, Page::After(_));
+        let (condition, order, cursor) = page_condition(page);
+
+        // `created` and `id` are selected again for the order to refer to
+        let mut query = self
+            .surreal
+            .query(format!(
+                "SELECT $this AS message, search::offsets(1)[0] AS highlights, created, id \
+                FROM message \
+                WHERE text @1@ $query AND group.members CONTAINS $member AND group.deleted = NONE \
+                AND ($group = NONE OR group = $group) \
+                AND ($author = NONE OR author = $author) \
+                AND ($since = NONE OR created >= $since) \
+                AND ($until = NONE OR created < $until) \
+                {condition} ORDER BY created {order}, id {order} LIMIT $count"
+            ))
+            .bind(("query", search.query))
+            .bind(("member", search.member))
+            .bind(("group", search.group))
+            .bind(("author", search.author))
+            .bind(("since", search.since))
+            .bind(("until", search.until))
+            .bind(("count", count));
+        if let Some(cursor) = cursor {
+            query = query
+                .bind(("created", cursor.created))
+                .bind(("id", cursor.id));
+        }
+
+        let mut hits: Vec<SearchHit> = query.await?.take(0)?;
+        if ascending {
+            hits.reverse();
+        }
+
+        Ok(hits)
+    }
+
+    async fn edit_message(
+        &self,
+        id: RecordId,
+        text: String,
+        mentions: Vec<RecordId>,
+        edited: i64,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("BEGIN TRANSACTION")
+            .query(
+                "LET $message = (UPDATE $id SET revisions += { text: text, replaced: $edited }, \
+                text = $text, mentions = $mentions, edited = $edited WHERE deleted = NONE)[0]",
+            )
+            .query(
+                "IF $message != NONE { \
+                    DELETE mention WHERE message = $id AND $mentions CONTAINSNOT user; \
+                    LET $notified = (SELECT VALUE user FROM mention WHERE message = $id); \
+                    FOR $user IN $mentions { \
+                        IF $user != $message.author AND $notified CONTAINSNOT $user { \
+                            CREATE mention CONTENT { user: $user, message: $id, group: $message.group, created: $edited } \
+                        } \
+                    } \
+                }",
+            )
+            .query("RETURN $message")
+            .query("COMMIT TRANSACTION")
+            .bind(("id", id))
+            .bind(("text", text))
+            .bind(("mentions", mentions))
+            .bind(("edited", edited))
+            .await?;
+
+        // The `RETURN` is the only result of the transaction
+        res.take(0)
+    }
+
+    async fn delete_message(
+        &self,
+        id: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Message>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("DELETE reaction WHERE message = $id")
+            .query("DELETE mention WHERE message = $id")
+            .query("DELETE attachment WHERE message = $id")
+            .query("UPDATE $id SET text = '', revisions = [], deleted = $deleted")
+            .query("IF record::exists($id) { UPDATE $id.group SET pinned -= $id }")
+            .bind(("id", id))
+            .bind(("deleted", deleted))
+            .await?;
+
+        res.take(3)
+    }
+
+    async fn add_reaction(
+        &self,
+        reaction: CreateReaction,
+    ) -> Result<Option<Reaction>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("LET $id = type::thing('reaction', [$reaction.message, $reaction.user, $reaction.emoji])")
+            .query("IF record::exists($id) { RETURN NONE } ELSE { RETURN CREATE ONLY $id CONTENT $reaction }")
+            .bind(("reaction", reaction))
+            .await?;
+
+        res.take(1)
+    }
+
+    async fn remove_reaction(
+        &self,
+        message: RecordId,
+        user: RecordId,
+        emoji: String,
+    ) -> Result<Option<Reaction>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("DELETE type::thing('reaction', [$message, $user, $emoji]) RETURN BEFORE")
+            .bind(("message", message))
+            .bind(("user", user))
+            .bind(("emoji", emoji))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn count_reactions(
+        &self,
+        messages: Vec<RecordId>,
+        user: &RecordId,
+    ) -> Result<Vec<ReactionCount>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query(
+                "SELECT message, emoji, count, mine > 0 AS mine FROM (\
+                    SELECT message, emoji, count() AS count, count(user = $user) AS mine \
+                    FROM reaction WHERE message IN $messages GROUP BY message, emoji\
+                )",
+            )
+            .bind(("messages", messages))
+            .bind(("user", user.clone()))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn mark_read(
+        &self,
+        marker: CreateReadMarker,
+    ) -> Result<Option<ReadMarker>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("LET $id = type::thing('read_marker', [$marker.group, $marker.user])")
+            .query(
+                "IF record::exists($id) AND ($id.message_created > $marker.message_created \
+                    OR ($id.message_created = $marker.message_created AND $id.message >= $marker.message)) \
+                { RETURN SELECT * FROM ONLY $id } ELSE { RETURN UPSERT ONLY $id CONTENT $marker }",
+            )
+            .bind(("marker", marker))
+            .await?;
+
+        res.take(1)
+    }
+
+    async fn get_read_markers(
+        &self,
+        group: &RecordId,
+    ) -> Result<Vec<ReadMarker>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM read_marker WHERE group = $group")
+            .bind(("group", group.clone()))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn count_unread(
+        &self,
+        user: &RecordId,
+        groups: Vec<RecordId>,
+    ) -> Result<Vec<UnreadCount>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query(
+                // Counted per group, `group IN $groups` doesn't work with the compound index
+                "SELECT * FROM (\
+                    SELECT id AS group, (\
+                        SELECT count() FROM (\
+                            SELECT created, id, type::thing('read_marker', [group, $user]) AS marker \
+                            FROM message WHERE group = $parent.id AND author != $user AND deleted = NONE\
+                        ) WHERE marker.message_created = NONE OR created > marker.message_created \
+                        OR (created = marker.message_created AND id > marker.message) GROUP ALL\
+                    )[0].count ?? 0 AS count FROM $groups\
+                ) WHERE count > 0",
+            )
+            .bind(("user", user.clone()))
+            .bind(("groups", groups))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn create_mentions(
+        &self,
+        mentions: Vec<CreateMention>,
+    ) -> Result<Vec<Mention>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("INSERT INTO mention $mentions")
+            .bind(("mentions", mentions))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_mention(&self, id: &str) -> Result<Option<Mention>, surrealdb::Error> {
+        self.surreal.select(("mention", id)).await
+    }
+
+    async fn get_unread_mentions(
+        &self,
+        user: &RecordId,
+        page: Page,
+        count: u64,
+    ) -> Result<Vec<Mention>, surrealdb::Error> {
+        self.page_records(
+            "mention",
+            "user = $scope AND read = NONE AND group.deleted = NONE",
+            user,
+            page,
+            count,
+        )
+        .await
+    }
+
+    async fn read_mention(
+        &self,
+        id: RecordId,
+        read: i64,
+    ) -> Result<Option<Mention>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $id SET read = $read")
+            .bind(("id", id))
+            .bind(("read", read))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn create_attachment(
+        &self,
+        attachment: CreateAttachment,
+    ) -> Result<Option<Attachment>, surrealdb::Error> {
+        self.surreal.create("attachment").content(attachment).await
+    }
+
+    async fn get_attachment(&self, id: &str) -> Result<Option<Attachment>, surrealdb::Error> {
+        self.surreal.select(("attachment", id)).await
+    }
+
+    async fn get_attachments(
+        &self,
+        messages: Vec<RecordId>,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM attachment WHERE message IN $messages ORDER BY created ASC")
+            .bind(("messages", messages))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn attach_to_message(
+        &self,
+        ids: Vec<RecordId>,
+        message: RecordId,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $ids SET message = $message WHERE message = NONE")
+            .bind(("ids", ids))
+            .bind(("message", message))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn remove_attachment(
+        &self,
+        id: RecordId,
+    ) -> Result<Option<Attachment>, surrealdb::Error> {
+        self.surreal.delete(id).await
+    }
+
+    async fn remove_unsent_attachments(
+        &self,
+        before: i64,
+    ) -> Result<Vec<Attachment>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("DELETE attachment WHERE message = NONE AND created < $before RETURN BEFORE")
+            .bind(("before", before))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_group(&self, id: &str) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM $id WHERE deleted = NONE")
+            .bind(("id", RecordId::from(("group", id))))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_groups_by_member(
+        &self,
+        member: RecordId,
+        offset: u64,
+        count: u64,
+    ) -> Result<Vec<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query(
+                "SELECT * FROM group WHERE members CONTAINS $member AND deleted = NONE \
+                START $offset LIMIT $count",
+            )
+            .bind(("member", member))
+            .bind(("offset", offset))
+            .bind(("count", count))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn create_group(&self, group: CreateGroup) -> Result<Option<Group>, surrealdb::Error> {
+        self.surreal.create("group").content(group).await
+    }
+
+    async fn open_direct_group(
+        &self,
+        group: CreateGroup,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("LET $existing = (SELECT * FROM group WHERE direct = $group.direct)[0]")
+            .query(
+                "IF $existing != NONE { RETURN $existing } \
+                ELSE { RETURN CREATE ONLY group CONTENT $group }",
+            )
+            .bind(("group", group))
+            .await?;
+
+        res.take(1)
+    }
+
+    async fn add_member_to_group(
+        &self,
+        group: RecordId,
+        member: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $group SET members = array::union(members, [$member])")
+            .bind(("group", group))
+            .bind(("member", member))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn update_group(
+        &self,
+        group: RecordId,
+        settings: GroupSettings,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        // Fields left out of `$settings` are NONE, which unsets them
+        let mut res = self
+            .surreal
+            .query(
+                "UPDATE $group SET name = $settings.name, \
+                description = $settings.description, topic = $settings.topic",
+            )
+            .bind(("group", group))
+            .bind(("settings", settings))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn set_group_icon(
+        &self,
+        group: RecordId,
+        icon: Option<String>,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        // `option<string>` fields can't be set to NULL, they have to be unset
+        let query = match icon {
+            Some(_) => "UPDATE $group SET icon = $icon",
+            None => "UPDATE $group UNSET icon",
+        };
+
+        let mut res = self
+            .surreal
+            .query(query)
+            .bind(("group", group))
+            .bind(("icon", icon))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn set_role(
+        &self,
+        group: RecordId,
+        member: RecordId,
+        role: Role,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let change = match role {
+            Role::ReadOnly => "read_only = array::union(read_only, [$member]), admins -= $member",
+            Role::Member => "admins -= $member, read_only -= $member",
+            Role::Admin => "admins = array::union(admins, [$member]), read_only -= $member",
+            Role::Owner => {
+                "admins = array::union(array::complement(admins, [$member]), [owner]), \
+                read_only -= $member, owner = $member"
+            }
+        };
+
+        let mut res = self
+            .surreal
+            .query(format!(
+                "UPDATE $group SET {change} WHERE members CONTAINS $member AND owner != $member"
+            ))
+            .bind(("group", group))
+            .bind(("member", member))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn pin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+        limit: usize,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query(
+                "UPDATE $group SET pinned += $message \
+                WHERE pinned CONTAINSNOT $message AND array::len(pinned) < $limit",
+            )
+            .bind(("group", group))
+            .bind(("message", message))
+            .bind(("limit", limit))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn unpin_message(
+        &self,
+        group: RecordId,
+        message: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $group SET pinned -= $message")
+            .bind(("group", group))
+            .bind(("message", message))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn remove_member_from_group(
+        &self,
+        group: RecordId,
+        member: RecordId,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $group SET members -= $member, admins -= $member, read_only -= $member")
+            .bind(("group", group))
+            .bind(("member", member))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn delete_group(
+        &self,
+        group: RecordId,
+        deleted: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("UPDATE $group SET deleted = $deleted WHERE deleted = NONE")
+            .bind(("group", group))
+            .bind(("deleted", deleted))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn restore_group(
+        &self,
+        group: RecordId,
+        owner: RecordId,
+        since: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query(
+                "UPDATE $group UNSET deleted \
+                WHERE owner = $owner AND deleted != NONE AND deleted >= $since",
+            )
+            .bind(("group", group))
+            .bind(("owner", owner))
+            .bind(("since", since))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn purge_groups(&self, before: i64) -> Result<PurgedGroups, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("BEGIN TRANSACTION")
+            // `NONE < $before` holds, groups which aren't deleted have to be excluded
+            .query("LET $groups = SELECT VALUE id FROM group WHERE deleted != NONE AND deleted < $before")
+            // `group IN $groups` doesn't work with the compound index
+            .query("LET $messages = SELECT VALUE id FROM message WHERE $groups CONTAINS group")
+            .query("DELETE reaction WHERE $messages CONTAINS message")
+            .query("DELETE mention WHERE $groups CONTAINS group")
+            .query("LET $attachments = DELETE attachment WHERE $groups CONTAINS group RETURN BEFORE")
+            .query("DELETE message WHERE $groups CONTAINS group")
+            .query("DELETE read_marker WHERE $groups CONTAINS group")
+            .query("DELETE invite WHERE $groups CONTAINS group")
+            .query("LET $icons = (DELETE $groups RETURN BEFORE).filter(|$group| $group.icon != NONE).map(|$group| $group.icon)")
+            .query("RETURN { attachments: $attachments, icons: $icons }")
+            .query("COMMIT TRANSACTION")
+            .bind(("before", before))
+            .await?;
+
+        // The `RETURN` is the only result of the transaction
+        let purged: Option<PurgedGroups> = res.take(0)?;
+        Ok(purged.unwrap_or_default())
+    }
+
+    async fn get_invite(&self, code: &str) -> Result<Option<Invite>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM ONLY invite WHERE code = $code LIMIT 1")
+            .bind(("code", code.to_string()))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn get_invites(&self, group: &RecordId) -> Result<Vec<Invite>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("SELECT * FROM invite WHERE group = $group ORDER BY created DESC")
+            .bind(("group", group.clone()))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn create_invite(
+        &self,
+        invite: CreateInvite,
+    ) -> Result<Option<Invite>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("CREATE ONLY invite CONTENT $invite")
+            .bind(("invite", invite))
+            .await?;
+
+        res.take(0)
+    }
+
+    async fn use_invite(
+        &self,
+        invite: RecordId,
+        member: RecordId,
+        now: i64,
+    ) -> Result<Option<Group>, surrealdb::Error> {
+        let mut res = self
+            .surreal
+            .query("BEGIN TRANSACTION")
+            .query(
+                "LET $used = (UPDATE $invite SET uses += 1 \
+                WHERE (expires = NONE OR expires > $now) AND (max_uses = NONE OR uses < max_uses))[0]",
+            )
+            .query(
+                "IF $used = NONE { RETURN NONE } \
+                ELSE { RETURN UPDATE ONLY $used.group SET members += $member }",
+            )
+            .query("COMMIT TRANSACTION")
+            .bind(("invite", invite))
+            .bind(("member", member))
+            .bind(("now", now))
+            .await?;
+
+        // The `RETURN` is the only result of the transaction
+        res.take(0)
+    }
+
+    async fn remove_invite(&self, id: RecordId) -> Result<Option<Invite>, surrealdb::Error> {
+        self.surreal.delete(id).await
+    }
+}
+
+impl Fairing for DBConnection {
+    fn info(&self) -> rocket::fairing::Info {
+        rocket::fairing::Info {
+            name: "database_connection",
+            kind: rocket::fairing::Kind::Ignite,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{open_surreal, Snapshot, TestStore};
+
+    #[rocket::async_trait]
+    impl TestStore for DBConnection {
+        async fn snapshot(&self) -> Snapshot {
+            let mut res = self
+                .surreal
+                .query("SELECT * FROM user")
+                .query("SELECT * FROM session")
+                .query("SELECT * FROM group")
+                .query("SELECT * FROM message")
+                .await
+                .unwrap();
+            let users: Vec<User> = res.take(0).unwrap();
+            let sessions: Vec<Session> = res.take(1).unwrap();
+            let groups: Vec<Group> = res.take(2).unwrap();
+            let messages: Vec<Message> = res.take(3).unwrap();
+            Snapshot::new(&users, &sessions, &groups, &messages)
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_upgrades_existing_records() {
+        let db = open_surreal().await;
+        db.apply(&MIGRATIONS[0]).await.unwrap();
+        db.surreal
+            .query("CREATE user:alice CONTENT { email: 'alice@example.com', password: 'hash' }")
+            .query(
+                "CREATE group:general CONTENT { owner: user:alice, name: 'general', members: [user:alice], created: 0 }",
+            )
+            .query(
+                "CREATE message:hello CONTENT { group: group:general, author: user:alice, text: 'hello', created: 0 }",
+            )
+            .await
+            .unwrap()
+            .check()
+            .unwrap();
+
+        let applied = db.migrate().await.unwrap();
+        assert_eq!(applied, MIGRATIONS.len() - 1);
+        assert_eq!(
+            db.schema_version().await.unwrap(),
+            migration::latest_version()
+        );
+
+        let id = RecordId::from(("message", "hello"));
+        let edited = db
+            .edit_message(id.clone(), "hello!".to_string(), Vec::new(), 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.text, "hello!");
+        assert_eq!(edited.revisions.len(), 1);
+        assert_eq!(edited.revisions[0].text, "hello");
+        assert!(!edited.system);
+
+        let group = db.get_group("general").await.unwrap().unwrap();
+        assert!(group.pinned.is_empty());
+    }
+
+    #[tokio::test]
+    async fn migrate_refuses_a_newer_database() {
+        let db = open_surreal().await;
+        db.migrate().await.unwrap();
+        let newer = migration::latest_version() + 1;
+        db.surreal
+            .query(
+                "CREATE type::thing('migration', $version) CONTENT { version: $version, name: 'future', applied: 0 }",
+            )
+            .bind(("version", newer))
+            .await
+            .unwrap()
+            .check()
+            .unwrap();
+
+        let error = db.migrate().await.unwrap_err();
+        assert!(matches!(
+            error,
+            MigrationError::NewerVersion { database, binary }
+                if database == newer && binary == migration::latest_version()
+        ));
+    }
+}
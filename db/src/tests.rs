@@ -0,0 +1,541 @@
+//! Tests every [`ChatStore`] has to pass, run against each store
+
+use surrealdb::RecordId;
+
+use crate::{
+    ChatStore, Config, CreateAttachment, CreateGroup, CreateInvite, CreateMention, CreateMessage,
+    CreateReaction, CreateSession, CreateUser, Cursor, DBConnection, Group, MemoryStore, Message,
+    Page, Session, User,
+};
+
+/// Inputs trying to break out of a query or point at another record
+const HOSTILE_INPUTS: &[&str] = &[
+    "\"; DELETE user; \"",
+    "'; DELETE group; --",
+    "x' OR '1' = '1",
+    "-- REMOVE TABLE message",
+    "a; UPDATE user SET password = 'pwned'",
+    "user:alice",
+    "group:⟨general⟩",
+    "⟩; DELETE session; ⟨",
+    "`; DELETE user; `",
+];
+
+/// A store the shared tests can look into
+#[rocket::async_trait]
+pub(crate) trait TestStore: ChatStore {
+    /// Every user, session, group and message, to compare before and after a call
+    async fn snapshot(&self) -> Snapshot;
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Snapshot {
+    users: Vec<String>,
+    sessions: Vec<String>,
+    groups: Vec<String>,
+    messages: Vec<String>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(
+        users: &[User],
+        sessions: &[Session],
+        groups: &[Group],
+        messages: &[Message],
+    ) -> Self {
+        fn sorted(mut records: Vec<String>) -> Vec<String> {
+            records.sort();
+            records
+        }
+
+        Self {
+            users: sorted(
+                users
+                    .iter()
+                    .map(|u| format!("{} {} {}", u.id, u.email, u.password))
+                    .collect(),
+            ),
+            sessions: sorted(
+                sessions
+                    .iter()
+                    .map(|s| format!("{} {}", s.id, s.user))
+                    .collect(),
+            ),
+            groups: sorted(
+                groups
+                    .iter()
+                    .map(|g| format!("{} {} {:?}", g.id, g.name, g.members))
+                    .collect(),
+            ),
+            messages: sorted(
+                messages
+                    .iter()
+                    .map(|m| format!("{} {} {}", m.id, m.author, m.text))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// An empty SurrealDB database without any migrations applied
+pub(crate) async fn open_surreal() -> DBConnection {
+    let config = Config {
+        address: "mem://".to_string(),
+        namespace: "test".to_string(),
+        database: "test".to_string(),
+        auth: crate::AuthLevel::Root,
+        username: None,
+        password: None,
+        token_path: None,
+        auto_migrate: true,
+    };
+    DBConnection::new(&config).await.unwrap()
+}
+
+/// Each store seeded with the user `alice`, her session, a group and a message
+async fn seeded() -> Vec<(Box<dyn TestStore>, Group)> {
+    let surreal = open_surreal().await;
+    surreal.migrate().await.unwrap();
+    let stores: [Box<dyn TestStore>; 2] = [Box::new(MemoryStore::new()), Box::new(surreal)];
+
+    let mut seeded = Vec::new();
+    for store in stores {
+        let alice = store
+            .create_user(
+                "alice",
+                CreateUser {
+                    email: "alice@example.com".to_string(),
+                    password: "hash".to_string(),
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        store
+            .create_session(
+                "token",
+                CreateSession {
+                    user: alice.id.clone(),
+                    created: 0,
+                },
+            )
+            .await
+            .unwrap();
+        let group = store
+            .create_group(CreateGroup {
+                owner: alice.id.clone(),
+                name: "general".to_string(),
+                members: vec![alice.id.clone()],
+                created: 0,
+                direct: None,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        store
+            .create_message(message(&group, "hello", 0, Vec::new()))
+            .await
+            .unwrap();
+        seeded.push((store, group));
+    }
+
+    seeded
+}
+
+/// A message by the owner of `group`
+fn message(group: &Group, text: &str, created: i64, mentions: Vec<RecordId>) -> CreateMessage {
+    CreateMessage {
+        group: group.id.clone(),
+        author: group.owner.clone(),
+        text: text.to_string(),
+        created,
+        reply_to: None,
+        mentions,
+        system: false,
+    }
+}
+
+#[tokio::test]
+async fn create_user_stores_hostile_input_verbatim() {
+    for (store, _) in seeded().await {
+        for (n, input) in HOSTILE_INPUTS.iter().enumerate() {
+            let before = store.snapshot().await;
+            let user = store
+                .create_user(
+                    input,
+                    CreateUser {
+                        email: format!("user{n}@example.com"),
+                        password: input.to_string(),
+                    },
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(user.id, RecordId::from(("user", *input)));
+            assert_eq!(user.password, *input);
+
+            // Only the new user was added
+            let after = store.snapshot().await;
+            assert_eq!(after.users.len(), before.users.len() + 1, "input: {input}");
+            assert!(before.users.iter().all(|u| after.users.contains(u)));
+            assert_eq!(before.sessions, after.sessions, "input: {input}");
+            assert_eq!(before.groups, after.groups, "input: {input}");
+            assert_eq!(before.messages, after.messages, "input: {input}");
+
+            // SurrealDB rejects most of these as emails, the rest is stored as is
+            let before = store.snapshot().await;
+            let created = store
+                .create_user(
+                    &format!("email{n}"),
+                    CreateUser {
+                        email: input.to_string(),
+                        password: "hash".to_string(),
+                    },
+                )
+                .await;
+            let after = store.snapshot().await;
+            match created {
+                Ok(Some(user)) => assert_eq!(user.email, *input),
+                _ => assert_eq!(before, after, "input: {input}"),
+            }
+            assert_eq!(before.sessions, after.sessions, "input: {input}");
+            assert_eq!(before.groups, after.groups, "input: {input}");
+            assert_eq!(before.messages, after.messages, "input: {input}");
+
+            let alice = store.get_user("alice").await.unwrap().unwrap();
+            assert_eq!(alice.password, "hash");
+        }
+    }
+}
+
+#[tokio::test]
+async fn get_user_by_email_matches_exactly() {
+    for (store, _) in seeded().await {
+        let before = store.snapshot().await;
+        for input in HOSTILE_INPUTS {
+            let user = store.get_user_by_email(input).await.unwrap();
+            assert!(user.is_none(), "input: {input}");
+        }
+        assert_eq!(before, store.snapshot().await);
+
+        let alice = store.get_user_by_email("alice@example.com").await.unwrap();
+        assert_eq!(alice.unwrap().id, RecordId::from(("user", "alice")));
+    }
+}
+
+#[tokio::test]
+async fn get_group_only_finds_its_own_record() {
+    for (store, group) in seeded().await {
+        let before = store.snapshot().await;
+        for input in HOSTILE_INPUTS {
+            let found = store.get_group(input).await.unwrap();
+            assert!(found.is_none(), "input: {input}");
+        }
+        assert_eq!(before, store.snapshot().await);
+
+        let key = group.id.key().to_string();
+        assert!(store.get_group(&key).await.unwrap().is_some());
+    }
+}
+
+#[tokio::test]
+async fn add_member_to_group_only_changes_the_group() {
+    for (store, group) in seeded().await {
+        for input in HOSTILE_INPUTS {
+            let before = store.snapshot().await;
+            let member = RecordId::from(("user", *input));
+            let updated = store
+                .add_member_to_group(group.id.clone(), member.clone())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(updated.members.contains(&member), "input: {input}");
+
+            let after = store.snapshot().await;
+            assert_eq!(before.users, after.users, "input: {input}");
+            assert_eq!(before.sessions, after.sessions, "input: {input}");
+            assert_eq!(before.messages, after.messages, "input: {input}");
+
+            // A hostile group id doesn't reach any existing group
+            let missing = RecordId::from(("group", *input));
+            let before = store.snapshot().await;
+            let updated = store.add_member_to_group(missing, member).await.unwrap();
+            assert!(updated.is_none(), "input: {input}");
+            assert_eq!(before, store.snapshot().await, "input: {input}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn add_member_to_group_twice_keeps_one_entry() {
+    for (store, group) in seeded().await {
+        let bob = RecordId::from(("user", "bob"));
+        for _ in 0..2 {
+            let updated = store
+                .add_member_to_group(group.id.clone(), bob.clone())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(updated.members.iter().filter(|m| **m == bob).count(), 1);
+        }
+    }
+}
+
+#[tokio::test]
+async fn get_messages_since_resumes_within_the_same_millisecond() {
+    for (store, group) in seeded().await {
+        let mut sent = Vec::new();
+        for text in ["a", "b", "c", "d"] {
+            let message = store
+                .create_message(message(&group, text, 1, Vec::new()))
+                .await
+                .unwrap()
+                .unwrap();
+            sent.push(message.id);
+        }
+        sent.sort_by_key(|id| id.key().to_string());
+
+        // Page through everything after the seeded message, two at a time
+        let mut since = Cursor {
+            created: 0,
+            id: RecordId::from(("message", "")),
+        };
+        let mut received = Vec::new();
+        loop {
+            let page = store
+                .get_messages_since(&group.id, since.clone(), 2)
+                .await
+                .unwrap();
+            let Some(last) = page.last() else {
+                break;
+            };
+            since = Cursor::from(last);
+            received.extend(page.into_iter().map(|m| m.id));
+        }
+        let received: Vec<RecordId> = received
+            .into_iter()
+            .filter(|id| sent.contains(id))
+            .collect();
+        assert_eq!(received, sent);
+    }
+}
+
+#[tokio::test]
+async fn edit_message_updates_the_mention_inbox() {
+    for (store, group) in seeded().await {
+        let alice = group.owner.clone();
+        let bob = RecordId::from(("user", "bob"));
+        let carol = RecordId::from(("user", "carol"));
+        let message = store
+            .create_message(message(&group, "@bob", 1, vec![bob.clone()]))
+            .await
+            .unwrap()
+            .unwrap();
+        store
+            .create_mentions(vec![CreateMention {
+                user: bob.clone(),
+                message: message.id.clone(),
+                group: group.id.clone(),
+                created: 1,
+            }])
+            .await
+            .unwrap();
+
+        let inbox = |user: RecordId| {
+            let store = &store;
+            async move {
+                store
+                    .get_unread_mentions(&user, Page::Latest, 10)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let edited = store
+            .edit_message(
+                message.id.clone(),
+                "@carol @alice".to_string(),
+                vec![carol.clone(), alice.clone()],
+                2,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.mentions, vec![carol.clone(), alice.clone()]);
+        assert!(inbox(bob.clone()).await.is_empty());
+        assert!(inbox(alice.clone()).await.is_empty());
+        let notified = inbox(carol.clone()).await;
+        assert_eq!(notified.len(), 1);
+
+        // Staying mentioned keeps the same inbox entry
+        store
+            .edit_message(
+                message.id.clone(),
+                "@carol!".to_string(),
+                vec![carol.clone()],
+                3,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let still_notified = inbox(carol).await;
+        assert_eq!(still_notified.len(), 1);
+        assert_eq!(still_notified[0].id, notified[0].id);
+    }
+}
+
+#[tokio::test]
+async fn purge_groups_returns_the_icons_of_purged_groups() {
+    for (store, group) in seeded().await {
+        let kept = store
+            .create_group(CreateGroup {
+                owner: group.owner.clone(),
+                name: "kept".to_string(),
+                members: vec![group.owner.clone()],
+                created: 0,
+                direct: None,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        for (group, icon) in [(&group, "purged"), (&kept, "kept")] {
+            store
+                .set_group_icon(group.id.clone(), Some(icon.to_string()))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        store.delete_group(group.id.clone(), 1).await.unwrap();
+
+        let purged = store.purge_groups(2).await.unwrap();
+        assert_eq!(purged.icons, ["purged"]);
+        let kept = store.get_group(&kept.id.key().to_string()).await.unwrap();
+        assert_eq!(kept.unwrap().icon.as_deref(), Some("kept"));
+    }
+}
+
+#[tokio::test]
+async fn purge_groups_removes_the_messages_of_purged_groups() {
+    for (store, group) in seeded().await {
+        let new_group = |name: &str| CreateGroup {
+            owner: group.owner.clone(),
+            name: name.to_string(),
+            members: vec![group.owner.clone()],
+            created: 0,
+            direct: None,
+        };
+        let kept = store
+            .create_group(new_group("kept"))
+            .await
+            .unwrap()
+            .unwrap();
+        let recent = store
+            .create_group(new_group("recent"))
+            .await
+            .unwrap()
+            .unwrap();
+        for group in [&group, &kept, &recent] {
+            let message = store
+                .create_message(message(group, "hi", 1, Vec::new()))
+                .await
+                .unwrap()
+                .unwrap();
+            store
+                .add_reaction(CreateReaction {
+                    message: message.id,
+                    user: group.owner.clone(),
+                    emoji: "👍".to_string(),
+                    created: 1,
+                })
+                .await
+                .unwrap();
+            store
+                .create_attachment(CreateAttachment {
+                    group: group.id.clone(),
+                    uploader: group.owner.clone(),
+                    name: "notes.txt".to_string(),
+                    size: 1,
+                    mime: "text/plain".to_string(),
+                    checksum: String::new(),
+                    created: 1,
+                })
+                .await
+                .unwrap();
+            store
+                .create_invite(CreateInvite {
+                    code: group.name.clone(),
+                    group: group.id.clone(),
+                    creator: group.owner.clone(),
+                    created: 1,
+                    expires: None,
+                    max_uses: None,
+                })
+                .await
+                .unwrap();
+        }
+        store.delete_group(group.id.clone(), 1).await.unwrap();
+        store.delete_group(recent.id.clone(), 3).await.unwrap();
+
+        let purged = store.purge_groups(2).await.unwrap();
+        assert_eq!(purged.attachments.len(), 1);
+        assert_eq!(purged.attachments[0].group, group.id);
+
+        let messages = |group: RecordId| {
+            let store = &store;
+            async move { store.get_messages(&group, Page::Latest, 10).await.unwrap() }
+        };
+        assert!(messages(group.id.clone()).await.is_empty());
+        assert!(store.get_invite("general").await.unwrap().is_none());
+        for kept in [&kept, &recent] {
+            let messages = messages(kept.id.clone()).await;
+            assert_eq!(messages.len(), 1, "{}", kept.name);
+            let reactions = store
+                .count_reactions(vec![messages[0].id.clone()], &kept.owner)
+                .await
+                .unwrap();
+            assert_eq!(reactions.len(), 1, "{}", kept.name);
+            assert!(store.get_invite(&kept.name).await.unwrap().is_some());
+        }
+    }
+}
+
+#[tokio::test]
+async fn remove_unsent_attachments_keeps_sent_and_recent_ones() {
+    for (store, group) in seeded().await {
+        let upload = |name: &str, created: i64| CreateAttachment {
+            group: group.id.clone(),
+            uploader: group.owner.clone(),
+            name: name.to_string(),
+            size: 1,
+            mime: "text/plain".to_string(),
+            checksum: String::new(),
+            created,
+        };
+        let mut ids = Vec::new();
+        for (name, created) in [("unsent", 1), ("sent", 1), ("recent", 3)] {
+            let attachment = store
+                .create_attachment(upload(name, created))
+                .await
+                .unwrap()
+                .unwrap();
+            ids.push(attachment.id);
+        }
+        let message = store
+            .create_message(message(&group, "notes", 1, Vec::new()))
+            .await
+            .unwrap()
+            .unwrap();
+        store
+            .attach_to_message(vec![ids[1].clone()], message.id)
+            .await
+            .unwrap();
+
+        let removed = store.remove_unsent_attachments(2).await.unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, ids[0]);
+        for (id, exists) in ids.iter().zip([false, true, true]) {
+            let found = store.get_attachment(&id.key().to_string()).await.unwrap();
+            assert_eq!(found.is_some(), exists, "{id}");
+        }
+    }
+}
@@ -1,7 +1,17 @@
 #![allow(private_interfaces)]
 
+pub mod attachment;
+pub mod cursor;
 pub mod group;
+pub mod invite;
+pub mod live;
+pub mod mention;
 pub mod message;
+pub mod permission;
+pub mod pin;
+pub mod read;
+pub mod search;
+pub mod typing;
 
 use rocket::{
     http::{ContentType, CookieJar},
@@ -26,7 +36,7 @@ enum PageResponse<'a> {
 #[get("/chat")]
 pub async fn home_page(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
 ) -> PageResponse<'static> {
     match session::verify(cookies, database).await {
         Some(Some(_)) => (),
@@ -47,7 +57,7 @@ pub async fn home_page(
 #[get("/chat/<group>")]
 pub async fn group_page(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
     group: &str,
 ) -> PageResponse<'static> {
     let session = match session::verify(cookies, database).await {
@@ -76,3 +86,25 @@ pub async fn group_page(
         ContentType::HTML,
     )
 }
+
+/// The page someone following an invite link lands on
+#[get("/invite/<_>")]
+pub async fn invite_page(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+) -> PageResponse<'static> {
+    match session::verify(cookies, database).await {
+        Some(Some(_)) => (),
+        Some(None) => {
+            return PageResponse::Unauthorized(Redirect::to(uri!("/login")));
+        }
+        None => {
+            return PageResponse::InternalError("Internal Database Error, try again later.");
+        }
+    }
+
+    PageResponse::Ok(
+        include_bytes!("../../content/chat/invite.html"),
+        ContentType::HTML,
+    )
+}
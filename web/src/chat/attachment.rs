@@ -0,0 +1,284 @@
+use std::io::Cursor;
+
+use rocket::{
+    form::Form,
+    fs::TempFile,
+    http::{ContentType, CookieJar},
+    request::Request,
+    response::{self, Responder, Response},
+    serde::json::Json,
+    tokio::io::AsyncReadExt,
+    State,
+};
+
+use crate::{
+    chat::permission::{self, Permission},
+    session, storage,
+};
+
+/// How many files can be sent with a single message
+pub const MAX_ATTACHMENTS: usize = 10;
+/// Longer file names are cut off
+const MAX_NAME_LEN: usize = 255;
+/// Images browsers can show without running anything, SVG can carry scripts
+const INLINE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+#[derive(Responder)]
+enum AttachmentResponse<T> {
+    #[response(status = 200)]
+    Ok(T),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 413)]
+    TooLarge(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub mime: String,
+}
+
+impl From<db::Attachment> for Attachment {
+    fn from(attachment: db::Attachment) -> Self {
+        Self {
+            id: attachment.id.key().to_string(),
+            name: attachment.name,
+            size: attachment.size,
+            mime: attachment.mime,
+        }
+    }
+}
+
+#[derive(FromForm)]
+struct Upload<'r> {
+    file: TempFile<'r>,
+}
+
+/// The content of an attachment, raster images are shown inline while
+/// everything else is downloaded, see [`INLINE_TYPES`]
+struct Download {
+    name: String,
+    mime: String,
+    data: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for Download {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let content_type = ContentType::parse_flexible(&self.mime).unwrap_or(ContentType::Binary);
+        let mime = format!("{}/{}", content_type.top(), content_type.sub()).to_ascii_lowercase();
+        let disposition = if INLINE_TYPES.contains(&mime.as_str()) {
+            "inline"
+        } else {
+            "attachment"
+        };
+
+        Response::build()
+            .header(content_type)
+            .raw_header(
+                "Content-Disposition",
+                format!(
+                    "{disposition}; filename*=UTF-8''{}",
+                    encode_filename(&self.name)
+                ),
+            )
+            .raw_header("X-Content-Type-Options", "nosniff")
+            // Attachments never change once uploaded
+            .raw_header("Cache-Control", "private, max-age=31536000, immutable")
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+/// Percent-encodes a file name for the `filename*` parameter of `Content-Disposition`
+fn encode_filename(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b".-_~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    encoded
+}
+
+/// The name the client gave the file, without any directories
+fn file_name(file: &TempFile<'_>) -> String {
+    let raw = file
+        .raw_name()
+        .map_or("", |name| name.dangerous_unsafe_unsanitized_raw().as_str());
+    let name: String = raw
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    if name.trim().is_empty() {
+        "file".to_string()
+    } else {
+        name
+    }
+}
+
+/// Uploads a file to a group, it is shown once sent with a message
+///
+/// Files which aren't sent within [`crate::purge::UNSENT_RETENTION`] are removed.
+#[post("/chat/<group>/attachment", data = "<upload>")]
+pub async fn upload(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    config: &State<storage::Config>,
+    group: &str,
+    upload: Form<Upload<'_>>,
+) -> AttachmentResponse<Json<Attachment>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return AttachmentResponse::Unauthorized(String::new()),
+        None => return AttachmentResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return AttachmentResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return AttachmentResponse::Unauthorized(e);
+    }
+
+    let file = &upload.file;
+    if file.len() > config.max_size {
+        return AttachmentResponse::TooLarge(format!(
+            "Files can't be larger than {} bytes.",
+            config.max_size
+        ));
+    }
+
+    let mime = file
+        .content_type()
+        .map(|ct| format!("{}/{}", ct.top(), ct.sub()).to_ascii_lowercase())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    if !config.allows(&mime) {
+        return AttachmentResponse::BadRequest(format!("Files of type `{mime}` aren't allowed."));
+    }
+
+    let mut data = Vec::with_capacity(file.len() as usize);
+    let read = match file.open().await {
+        Ok(mut reader) => reader.read_to_end(&mut data).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = read {
+        error!("Upload: {e:?}");
+        return AttachmentResponse::InternalServerError(String::new());
+    }
+
+    let attachment = match database
+        .create_attachment(db::CreateAttachment {
+            group: group.id,
+            uploader: session.user,
+            name: file_name(file),
+            size: data.len() as u64,
+            mime,
+            checksum: crypto::checksum(&data),
+            created: chrono::Utc::now().timestamp_millis(),
+        })
+        .await
+    {
+        Ok(Some(attachment)) => attachment,
+        Ok(None) => return AttachmentResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+    };
+
+    // Blobs are stored under the id of their attachment
+    if let Err(e) = blobs.put(&attachment.id.key().to_string(), &data).await {
+        error!("Storage: {e:?}");
+        if let Err(e) = database.remove_attachment(attachment.id).await {
+            error!("Database: {e:?}");
+        }
+        return AttachmentResponse::InternalServerError(String::new());
+    }
+
+    AttachmentResponse::Ok(Json(Attachment::from(attachment)))
+}
+
+/// Returns the content of an attachment to members of its group
+///
+/// Attachments which haven't been sent yet are only available to their uploader.
+#[get("/chat/<group>/attachment/<id>", rank = 2)]
+pub async fn download(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    group: &str,
+    id: &str,
+) -> AttachmentResponse<Download> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return AttachmentResponse::Unauthorized(String::new()),
+        None => return AttachmentResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return AttachmentResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return AttachmentResponse::Unauthorized(e);
+    }
+
+    let attachment = match database.get_attachment(id).await {
+        Ok(Some(attachment))
+            if attachment.group == group.id
+                && (attachment.message.is_some() || attachment.uploader == session.user) =>
+        {
+            attachment
+        }
+        Ok(_) => return AttachmentResponse::BadRequest("Attachment doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+    };
+
+    let data = match blobs.get(&attachment.id.key().to_string()).await {
+        Ok(Some(data)) => data,
+        Ok(None) => {
+            error!("Storage: Missing blob of {}", attachment.id);
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+        Err(e) => {
+            error!("Storage: {e:?}");
+            return AttachmentResponse::InternalServerError(String::new());
+        }
+    };
+
+    AttachmentResponse::Ok(Download {
+        name: attachment.name,
+        mime: attachment.mime,
+        data,
+    })
+}
@@ -0,0 +1,54 @@
+//! Opaque pagination cursors in the form of `<created>:<id>`
+
+/// Upper bound for `count` so a single request can't load a whole history
+pub const MAX_COUNT: u64 = 100;
+pub const DEFAULT_COUNT: u64 = 50;
+
+pub fn encode(cursor: &db::Cursor) -> String {
+    format!("{}:{}", cursor.created, cursor.id.key())
+}
+
+/// Parses a cursor pointing at a record of `table`
+pub fn decode(table: &str, cursor: &str) -> Option<db::Cursor> {
+    let (created, id) = cursor.split_once(':')?;
+    Some(db::Cursor {
+        created: created.parse().ok()?,
+        id: db::RecordId::from((table, id)),
+    })
+}
+
+/// Turns the `before` and `after` query parameters into a [`db::Page`]
+pub fn page(table: &str, before: Option<&str>, after: Option<&str>) -> Result<db::Page, String> {
+    match (before, after) {
+        (None, None) => Ok(db::Page::Latest),
+        (Some(before), None) => decode(table, before)
+            .map(db::Page::Before)
+            .ok_or_else(|| "Invalid `before` cursor.".to_string()),
+        (None, Some(after)) => decode(table, after)
+            .map(db::Page::After)
+            .ok_or_else(|| "Invalid `after` cursor.".to_string()),
+        (Some(_), Some(_)) => Err("Only one of `before` and `after` can be used.".to_string()),
+    }
+}
+
+/// The cursor to continue paging in the same direction, `None` once a page
+/// comes back short
+///
+/// `items` are expected newest first, as returned by [`db::ChatStore::get_messages`].
+pub fn next<T>(
+    page: &db::Page,
+    items: &[T],
+    count: u64,
+    cursor: impl Fn(&T) -> db::Cursor,
+) -> Option<String> {
+    if (items.len() as u64) < count {
+        return None;
+    }
+
+    let item = match page {
+        db::Page::After(_) => items.first(),
+        db::Page::Latest | db::Page::Before(_) => items.last(),
+    }?;
+
+    Some(encode(&cursor(item)))
+}
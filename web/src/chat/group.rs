@@ -1,6 +1,22 @@
-use rocket::{http::CookieJar, serde::json::Json, State};
+use rocket::{form::Form, fs::TempFile, http::CookieJar, serde::json::Json, State};
 
-use crate::session;
+use crate::{
+    chat::{
+        message,
+        permission::{self, Permission},
+    },
+    hub::{Event, GroupChange, Hub, MemberChange},
+    picture, purge, session, storage, user,
+};
+
+/// Longest group name, in characters
+const MAX_NAME_LEN: usize = 100;
+/// Longest topic, in characters
+const MAX_TOPIC_LEN: usize = 200;
+/// Longest description, in characters
+const MAX_DESCRIPTION_LEN: usize = 2000;
+/// Which variant of the group icon the group links to
+const ICON_SIZE: u32 = 64;
 
 #[derive(Responder)]
 enum GroupResponse<T> {
@@ -10,6 +26,8 @@ enum GroupResponse<T> {
     BadRequest(String),
     #[response(status = 401)]
     Unauthorized(String),
+    #[response(status = 413)]
+    TooLarge(String),
     #[response(status = 500)]
     InternalServerError(String),
 }
@@ -17,7 +35,33 @@ enum GroupResponse<T> {
 #[derive(serde::Serialize)]
 struct Group {
     pub id: String,
+    /// The other user's name for direct messages
     pub name: String,
+    pub direct: bool,
+    /// The role of the user in the group
+    pub role: db::Role,
+    /// Messages by other members the user hasn't read yet
+    pub unread: u64,
+    pub description: Option<String>,
+    pub topic: Option<String>,
+    /// URL of the group icon
+    pub icon: Option<String>,
+}
+
+impl Group {
+    /// The group as `user` sees it
+    fn new(group: db::Group, user: &db::RecordId, unread: u64) -> Self {
+        Self {
+            id: group.id.key().to_string(),
+            name: display_name(&group, user),
+            direct: group.direct.is_some(),
+            role: group.role_of(user).unwrap_or(db::Role::Member),
+            unread,
+            description: group.description,
+            topic: group.topic,
+            icon: group.icon.map(|icon| user::avatar_url(&icon, ICON_SIZE)),
+        }
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -25,16 +69,79 @@ struct CreateGroup<'a> {
     pub name: &'a str,
 }
 
+/// Settings left out stay as they are
+#[derive(serde::Deserialize)]
+struct UpdateGroup {
+    /// Owned since text with escapes, like line breaks, can't be borrowed from the JSON
+    pub name: Option<String>,
+    /// An empty description removes it
+    pub description: Option<String>,
+    /// An empty topic removes it
+    pub topic: Option<String>,
+}
+
+#[derive(FromForm)]
+struct IconUpload<'r> {
+    picture: TempFile<'r>,
+}
+
+#[derive(serde::Deserialize)]
+struct OpenDirect<'a> {
+    /// Id of the other user
+    pub id: &'a str,
+}
+
 #[derive(serde::Deserialize)]
 struct ChangeMembers<'a> {
     id: &'a str,
     is_remove: bool,
 }
 
+#[derive(serde::Deserialize)]
+struct TransferGroup<'a> {
+    /// Id of the member who becomes the owner
+    id: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+struct ChangeRole {
+    role: db::Role,
+}
+
+/// Trims a setting and checks its length, an empty setting is `None`
+fn clean_setting(value: &str, max_len: usize, setting: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if value.chars().count() > max_len {
+        return Err(format!(
+            "The {setting} can be at most {max_len} characters long."
+        ));
+    }
+
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+/// Group names are required and trimmed like every other setting
+fn clean_name(name: &str) -> Result<String, String> {
+    clean_setting(name, MAX_NAME_LEN, "name")?.ok_or_else(|| "A group needs a name.".to_string())
+}
+
+/// Direct messages are named after the other user, groups keep their name
+fn display_name(group: &db::Group, user: &db::RecordId) -> String {
+    if group.direct.is_none() {
+        return group.name.clone();
+    }
+
+    group
+        .members
+        .iter()
+        .find(|member| *member != user)
+        .map_or_else(|| group.name.clone(), |other| other.key().to_string())
+}
+
 #[get("/chat/groups/<count>/<offset>")]
 pub async fn get(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
     count: u64,
     offset: u64,
 ) -> GroupResponse<Json<Vec<Group>>> {
@@ -45,7 +152,7 @@ pub async fn get(
     };
 
     let db_groups = match database
-        .get_groups_by_member(session.user, offset, count)
+        .get_groups_by_member(session.user.clone(), offset, count)
         .await
     {
         Ok(groups) => groups,
@@ -55,11 +162,23 @@ pub async fn get(
         }
     };
 
+    let ids = db_groups.iter().map(|group| group.id.clone()).collect();
+    let unread_counts = match database.count_unread(&session.user, ids).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
     let groups: Vec<Group> = db_groups
         .into_iter()
-        .map(|group| Group {
-            id: group.id.key().to_string(),
-            name: group.name,
+        .map(|group| {
+            let unread = unread_counts
+                .iter()
+                .find(|c| c.group == group.id)
+                .map_or(0, |c| c.count);
+            Group::new(group, &session.user, unread)
         })
         .collect();
 
@@ -69,7 +188,7 @@ pub async fn get(
 #[post("/chat/create", format = "json", data = "<group>")]
 pub async fn create(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
     group: Json<CreateGroup<'_>>,
 ) -> GroupResponse<Json<Group>> {
     let session = match session::verify(cookies, database).await {
@@ -78,13 +197,19 @@ pub async fn create(
         None => return GroupResponse::InternalServerError(String::new()),
     };
 
+    let name = match clean_name(group.name) {
+        Ok(name) => name,
+        Err(e) => return GroupResponse::BadRequest(e),
+    };
+
     let created = chrono::Utc::now().timestamp_millis();
     let group = match database
         .create_group(db::CreateGroup {
             owner: session.user.clone(),
-            name: group.name.to_string(),
-            members: vec![session.user],
+            name,
+            members: vec![session.user.clone()],
             created,
+            direct: None,
         })
         .await
     {
@@ -98,16 +223,74 @@ pub async fn create(
         }
     };
 
-    GroupResponse::Ok(Json(Group {
-        id: group.id.key().to_string(),
-        name: group.name,
-    }))
+    GroupResponse::Ok(Json(Group::new(group, &session.user, 0)))
+}
+
+/// Opens the direct messages with another user, reusing them when they exist
+#[post("/chat/direct", format = "json", data = "<direct>")]
+pub async fn direct(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    direct: Json<OpenDirect<'_>>,
+) -> GroupResponse<Json<Group>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let other = match database.get_user(direct.id).await {
+        Ok(Some(other)) => other,
+        Ok(None) => {
+            return GroupResponse::BadRequest("A user with that id doesn't exist.".to_string())
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if other.id == session.user {
+        return GroupResponse::BadRequest("You can't message yourself.".to_string());
+    }
+
+    let group = match database
+        .open_direct_group(db::CreateGroup {
+            owner: session.user.clone(),
+            name: format!("{} & {}", session.user.key(), other.id.key()),
+            direct: Some(db::Group::direct_key(&session.user, &other.id)),
+            members: vec![session.user.clone(), other.id],
+            created: chrono::Utc::now().timestamp_millis(),
+        })
+        .await
+    {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    let unread = match database
+        .count_unread(&session.user, vec![group.id.clone()])
+        .await
+    {
+        Ok(counts) => counts.first().map_or(0, |c| c.count),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    GroupResponse::Ok(Json(Group::new(group, &session.user, unread)))
 }
 
 #[post("/chat/<group>/member", format = "json", data = "<change>")]
 pub async fn member(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
     group: &str,
     change: Json<ChangeMembers<'_>>,
 ) -> GroupResponse<()> {
@@ -128,11 +311,15 @@ pub async fn member(
         }
     };
 
-    if group.owner != session.user {
-        return GroupResponse::Unauthorized(
-            "Only the owner of a group is allowed to change members.".to_string(),
-        );
-    }
+    let permission = if change.is_remove {
+        Permission::Remove
+    } else {
+        Permission::Invite
+    };
+    let role = match permission::check(&group, &session.user, permission) {
+        Ok(role) => role,
+        Err(e) => return GroupResponse::Unauthorized(e),
+    };
 
     let member = match database.get_user(change.id).await {
         Ok(Some(member)) => member,
@@ -145,8 +332,30 @@ pub async fn member(
         }
     };
 
+    // Admins can't remove each other or the owner
+    if change.is_remove && group.role_of(&member.id).is_some_and(|r| r >= role) {
+        return GroupResponse::Unauthorized(
+            "Only members with a lower role than yours can be removed.".to_string(),
+        );
+    }
+
+    if !change.is_remove && group.members.contains(&member.id) {
+        return GroupResponse::BadRequest(
+            "The user is already a member of this group.".to_string(),
+        );
+    }
+
+    let event = MemberChange {
+        group: group.id.key().to_string(),
+        member: member.id.key().to_string(),
+        created: chrono::Utc::now().timestamp_millis(),
+    };
+
     if change.is_remove {
-        match database.remove_member_from_group(group.id, member.id).await {
+        match database
+            .remove_member_from_group(group.id.clone(), member.id)
+            .await
+        {
             Ok(Some(_)) => (),
             Ok(None) => return GroupResponse::InternalServerError(String::new()),
             Err(e) => {
@@ -154,8 +363,13 @@ pub async fn member(
                 return GroupResponse::InternalServerError(String::new());
             }
         }
+
+        hub.publish(&group.id, Event::MemberRemoved(event));
     } else {
-        match database.add_member_to_group(group.id, member.id).await {
+        match database
+            .add_member_to_group(group.id.clone(), member.id)
+            .await
+        {
             Ok(Some(_)) => (),
             Ok(None) => return GroupResponse::InternalServerError(String::new()),
             Err(e) => {
@@ -163,6 +377,486 @@ pub async fn member(
                 return GroupResponse::InternalServerError(String::new());
             }
         }
+
+        hub.publish(&group.id, Event::MemberAdded(event));
+    }
+
+    GroupResponse::Ok(())
+}
+
+/// Changes the role of a member, only the owner may change roles
+///
+/// Ownership is handed off with [`transfer`] instead.
+#[put("/chat/<group>/member/<id>/role", format = "json", data = "<change>")]
+pub async fn role(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    id: &str,
+    change: Json<ChangeRole>,
+) -> GroupResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::ManageRoles) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    if change.role == db::Role::Owner {
+        return GroupResponse::BadRequest(
+            "The owner role is given by transferring the group.".to_string(),
+        );
+    }
+
+    let member = db::RecordId::from(("user", id));
+    match database.set_role(group.id, member, change.role).await {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            return GroupResponse::BadRequest(
+                "The user isn't a member of this group or is its owner.".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    }
+
+    GroupResponse::Ok(())
+}
+
+/// Changes the name, description and topic of a group
+///
+/// Every change is recorded in the group's history as a system message.
+#[patch("/chat/<group>", format = "json", data = "<update>")]
+pub async fn update(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    update: Json<UpdateGroup>,
+) -> GroupResponse<Json<Group>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::EditGroup) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    let name = match update.name.as_deref().map(clean_name).transpose() {
+        Ok(name) => name.unwrap_or_else(|| group.name.clone()),
+        Err(e) => return GroupResponse::BadRequest(e),
+    };
+    let description = match update.description.as_deref() {
+        Some(description) => match clean_setting(description, MAX_DESCRIPTION_LEN, "description") {
+            Ok(description) => description,
+            Err(e) => return GroupResponse::BadRequest(e),
+        },
+        None => group.description.clone(),
+    };
+    let topic = match update.topic.as_deref() {
+        Some(topic) => match clean_setting(topic, MAX_TOPIC_LEN, "topic") {
+            Ok(topic) => topic,
+            Err(e) => return GroupResponse::BadRequest(e),
+        },
+        None => group.topic.clone(),
+    };
+
+    let mut changes = Vec::new();
+    if name != group.name {
+        changes.push(format!("renamed the group to \"{name}\""));
+    }
+    if description != group.description {
+        changes.push(match description {
+            Some(_) => "changed the description".to_string(),
+            None => "removed the description".to_string(),
+        });
+    }
+    if topic != group.topic {
+        changes.push(match &topic {
+            Some(topic) => format!("set the topic to \"{topic}\""),
+            None => "removed the topic".to_string(),
+        });
+    }
+
+    let group = if changes.is_empty() {
+        group
+    } else {
+        let settings = db::GroupSettings {
+            name,
+            description,
+            topic,
+        };
+        match database.update_group(group.id, settings).await {
+            Ok(Some(group)) => group,
+            Ok(None) => return GroupResponse::InternalServerError(String::new()),
+            Err(e) => {
+                error!("Database: {e:?}");
+                return GroupResponse::InternalServerError(String::new());
+            }
+        }
+    };
+
+    for change in changes {
+        if let Err(e) = message::post_system(database, hub, &group.id, &session.user, change).await
+        {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    }
+
+    let unread = match database
+        .count_unread(&session.user, vec![group.id.clone()])
+        .await
+    {
+        Ok(counts) => counts.first().map_or(0, |c| c.count),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    GroupResponse::Ok(Json(Group::new(group, &session.user, unread)))
+}
+
+/// Replaces the icon of a group
+#[post("/chat/<group>/icon", data = "<upload>")]
+pub async fn upload_icon(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    config: &State<storage::Config>,
+    hub: &State<Hub>,
+    group: &str,
+    upload: Form<IconUpload<'_>>,
+) -> GroupResponse<Json<Group>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::EditGroup) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    let icon = match picture::store(blobs, &upload.picture, config.max_size).await {
+        Ok(icon) => icon,
+        Err(e @ picture::UploadError::TooLarge) => {
+            return GroupResponse::TooLarge(e.message().to_string())
+        }
+        Err(e @ picture::UploadError::Unsupported) => {
+            return GroupResponse::BadRequest(e.message().to_string())
+        }
+        Err(e) => return GroupResponse::InternalServerError(e.message().to_string()),
+    };
+
+    set_icon(database, blobs, hub, group, session.user, Some(icon)).await
+}
+
+/// Removes the icon of a group
+#[delete("/chat/<group>/icon")]
+pub async fn remove_icon(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    hub: &State<Hub>,
+    group: &str,
+) -> GroupResponse<Json<Group>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::EditGroup) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    set_icon(database, blobs, hub, group, session.user, None).await
+}
+
+/// Points `group` at a new icon, deletes the previous one and records the
+/// change made by `user`
+async fn set_icon(
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    hub: &State<Hub>,
+    group: db::Group,
+    user: db::RecordId,
+    icon: Option<String>,
+) -> GroupResponse<Json<Group>> {
+    let previous = group.icon.clone();
+    // Removing an icon which isn't there changes nothing
+    let group = if icon.is_none() && previous.is_none() {
+        group
+    } else {
+        let change = match icon {
+            Some(_) => "changed the group icon",
+            None => "removed the group icon",
+        };
+
+        let group = match database.set_group_icon(group.id, icon.clone()).await {
+            Ok(Some(group)) => Some(group),
+            Ok(None) => None,
+            Err(e) => {
+                error!("Database: {e:?}");
+                None
+            }
+        };
+        let Some(group) = group else {
+            // Nothing points at the new icon
+            if let Some(icon) = icon {
+                picture::delete(blobs, &icon).await;
+            }
+            return GroupResponse::InternalServerError(String::new());
+        };
+
+        if let Some(previous) = previous {
+            picture::delete(blobs, &previous).await;
+        }
+
+        if let Err(e) =
+            message::post_system(database, hub, &group.id, &user, change.to_string()).await
+        {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+
+        group
+    };
+
+    let unread = match database.count_unread(&user, vec![group.id.clone()]).await {
+        Ok(counts) => counts.first().map_or(0, |c| c.count),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    GroupResponse::Ok(Json(Group::new(group, &user, unread)))
+}
+
+/// Leaves a group, the owner has to transfer it first
+#[post("/chat/<group>/leave")]
+pub async fn leave(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+) -> GroupResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if !group.members.contains(&session.user) {
+        return GroupResponse::BadRequest("You are not in this group.".to_string());
+    }
+
+    if group.direct.is_some() {
+        return GroupResponse::BadRequest("Direct messages can't be left.".to_string());
+    }
+
+    if group.owner == session.user {
+        return GroupResponse::BadRequest(
+            "Transfer the group to another member before leaving it.".to_string(),
+        );
+    }
+
+    match database
+        .remove_member_from_group(group.id.clone(), session.user.clone())
+        .await
+    {
+        Ok(Some(_)) => (),
+        Ok(None) => return GroupResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    }
+
+    let event = MemberChange {
+        group: group.id.key().to_string(),
+        member: session.user.key().to_string(),
+        created: chrono::Utc::now().timestamp_millis(),
+    };
+    hub.publish(&group.id, Event::MemberRemoved(event));
+
+    GroupResponse::Ok(())
+}
+
+/// Makes another member the owner, the current owner becomes an admin
+#[put("/chat/<group>/owner", format = "json", data = "<transfer>")]
+pub async fn transfer(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    transfer: Json<TransferGroup<'_>>,
+) -> GroupResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Transfer) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    let member = db::RecordId::from(("user", transfer.id));
+    match database.set_role(group.id, member, db::Role::Owner).await {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            return GroupResponse::BadRequest(
+                "The user isn't a member of this group or is its owner.".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    }
+
+    GroupResponse::Ok(())
+}
+
+/// Deletes a group, its owner can restore it until it's purged
+///
+/// See [`purge::RETENTION`] for how long that is.
+#[delete("/chat/<group>")]
+pub async fn delete(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+) -> GroupResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Delete) {
+        return GroupResponse::Unauthorized(e);
+    }
+
+    let deleted = chrono::Utc::now().timestamp_millis();
+    let group = match database.delete_group(group.id, deleted).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return GroupResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
+    };
+
+    let change = GroupChange {
+        group: group.id.key().to_string(),
+        user: session.user.key().to_string(),
+        created: deleted,
+    };
+    hub.publish(&group.id, Event::GroupDeleted(change));
+
+    GroupResponse::Ok(())
+}
+
+/// Restores a deleted group which hasn't been purged yet
+#[post("/chat/<group>/restore")]
+pub async fn restore(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+) -> GroupResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return GroupResponse::Unauthorized(String::new()),
+        None => return GroupResponse::InternalServerError(String::new()),
+    };
+
+    let since = chrono::Utc::now().timestamp_millis() - purge::RETENTION;
+    let group = db::RecordId::from(("group", group));
+    match database.restore_group(group, session.user, since).await {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            return GroupResponse::BadRequest(
+                "You don't own a deleted group with that id.".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return GroupResponse::InternalServerError(String::new());
+        }
     }
 
     GroupResponse::Ok(())
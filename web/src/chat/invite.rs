@@ -0,0 +1,319 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::permission::{self, Permission},
+    hub::{Event, Hub, MemberChange},
+    session,
+};
+
+#[derive(Responder)]
+enum InviteResponse<T> {
+    #[response(status = 200)]
+    Ok(Json<T>),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+#[derive(serde::Serialize)]
+struct Invite {
+    /// Shared as `/invite/<code>`
+    pub code: String,
+    pub group: String,
+    pub creator: String,
+    pub created: i64,
+    pub expires: Option<i64>,
+    pub max_uses: Option<u64>,
+    pub uses: u64,
+}
+
+impl From<db::Invite> for Invite {
+    fn from(invite: db::Invite) -> Self {
+        Self {
+            code: invite.code,
+            group: invite.group.key().to_string(),
+            creator: invite.creator.key().to_string(),
+            created: invite.created,
+            expires: invite.expires,
+            max_uses: invite.max_uses,
+            uses: invite.uses,
+        }
+    }
+}
+
+/// What someone following an invite gets to see before joining
+#[derive(serde::Serialize)]
+struct InviteInfo {
+    pub group: String,
+    pub name: String,
+    pub members: usize,
+    /// Whether the user is in the group already
+    pub joined: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct CreateInvite {
+    /// The invite can't be used from this time on, in milliseconds
+    pub expires: Option<i64>,
+    /// How often the invite can be used, unlimited when left out
+    pub max_uses: Option<u64>,
+}
+
+/// Creates an invite code for a group
+#[post("/chat/<group>/invite", format = "json", data = "<invite>")]
+pub async fn create(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    invite: Json<CreateInvite>,
+) -> InviteResponse<Invite> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return InviteResponse::Unauthorized(String::new()),
+        None => return InviteResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return InviteResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Invite) {
+        return InviteResponse::Unauthorized(e);
+    }
+
+    let created = chrono::Utc::now().timestamp_millis();
+    if invite.expires.is_some_and(|expires| expires <= created) {
+        return InviteResponse::BadRequest("An invite can't expire in the past.".to_string());
+    }
+    if invite.max_uses == Some(0) {
+        return InviteResponse::BadRequest("An invite has to be usable at least once.".to_string());
+    }
+
+    let invite = match database
+        .create_invite(db::CreateInvite {
+            code: crypto::generate_token(),
+            group: group.id,
+            creator: session.user,
+            created,
+            expires: invite.expires,
+            max_uses: invite.max_uses,
+        })
+        .await
+    {
+        Ok(Some(invite)) => invite,
+        Ok(None) => return InviteResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    InviteResponse::Ok(Json(Invite::from(invite)))
+}
+
+/// Returns the invites of a group which can still be used, newest first
+#[get("/chat/<group>/invites")]
+pub async fn get(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+) -> InviteResponse<Vec<Invite>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return InviteResponse::Unauthorized(String::new()),
+        None => return InviteResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return InviteResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Invite) {
+        return InviteResponse::Unauthorized(e);
+    }
+
+    let invites = match database.get_invites(&group.id).await {
+        Ok(invites) => invites,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let invites = invites
+        .into_iter()
+        .filter(|invite| invite.is_usable(now))
+        .map(Invite::from)
+        .collect();
+
+    InviteResponse::Ok(Json(invites))
+}
+
+/// Revokes an invite, it can't be used anymore afterwards
+#[delete("/chat/<group>/invite/<code>")]
+pub async fn revoke(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    code: &str,
+) -> InviteResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return InviteResponse::Unauthorized(String::new()),
+        None => return InviteResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return InviteResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Invite) {
+        return InviteResponse::Unauthorized(e);
+    }
+
+    let invite = match database.get_invite(code).await {
+        Ok(Some(invite)) if invite.group == group.id => invite,
+        Ok(_) => return InviteResponse::BadRequest("Invite doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = database.remove_invite(invite.id).await {
+        error!("Database: {e:?}");
+        return InviteResponse::InternalServerError(String::new());
+    }
+
+    InviteResponse::Ok(Json(()))
+}
+
+/// Returns the group an invite leads to
+#[get("/invite/<code>/info")]
+pub async fn info(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    code: &str,
+) -> InviteResponse<InviteInfo> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return InviteResponse::Unauthorized(String::new()),
+        None => return InviteResponse::InternalServerError(String::new()),
+    };
+
+    let invite = match database.get_invite(code).await {
+        Ok(Some(invite)) => invite,
+        Ok(None) => return InviteResponse::BadRequest("Invite doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if !invite.is_usable(chrono::Utc::now().timestamp_millis()) {
+        return InviteResponse::BadRequest(
+            "This invite has expired or has been used up.".to_string(),
+        );
+    }
+
+    let group = match database.get_group(&invite.group.key().to_string()).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return InviteResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    InviteResponse::Ok(Json(InviteInfo {
+        joined: group.members.contains(&session.user),
+        group: group.id.key().to_string(),
+        name: group.name,
+        members: group.members.len(),
+    }))
+}
+
+/// Joins the group of an invite, returns the id of the group
+///
+/// Members of the group don't use up the invite by accepting it.
+#[post("/invite/<code>")]
+pub async fn accept(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    code: &str,
+) -> InviteResponse<String> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return InviteResponse::Unauthorized(String::new()),
+        None => return InviteResponse::InternalServerError(String::new()),
+    };
+
+    let invite = match database.get_invite(code).await {
+        Ok(Some(invite)) => invite,
+        Ok(None) => return InviteResponse::BadRequest("Invite doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    let group = match database.get_group(&invite.group.key().to_string()).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return InviteResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    if group.members.contains(&session.user) {
+        return InviteResponse::Ok(Json(group.id.key().to_string()));
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let group = match database
+        .use_invite(invite.id, session.user.clone(), now)
+        .await
+    {
+        Ok(Some(group)) => group,
+        // Expired, or someone else used it up first
+        Ok(None) => {
+            return InviteResponse::BadRequest(
+                "This invite has expired or has been used up.".to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return InviteResponse::InternalServerError(String::new());
+        }
+    };
+
+    let event = MemberChange {
+        group: group.id.key().to_string(),
+        member: session.user.key().to_string(),
+        created: now,
+    };
+    hub.publish(&group.id, Event::MemberAdded(event));
+
+    InviteResponse::Ok(Json(group.id.key().to_string()))
+}
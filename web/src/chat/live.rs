@@ -0,0 +1,236 @@
+use std::convert::Infallible;
+
+use rocket::{
+    futures::{SinkExt, StreamExt},
+    http::CookieJar,
+    request::{FromRequest, Outcome},
+    response::stream::{Event, EventStream},
+    tokio::{self, sync::broadcast::error::RecvError},
+    Request, Shutdown, State,
+};
+use rocket_ws as ws;
+
+use crate::{
+    chat::{
+        cursor,
+        message::Message,
+        permission::{self, Permission},
+    },
+    hub, session,
+};
+
+/// The `Last-Event-ID` header sent by `EventSource` when it reconnects, the
+/// cursor of the last message it received
+pub struct LastEventId(Option<db::Cursor>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| cursor::decode("message", id));
+
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+#[derive(Responder)]
+enum LiveResponse<T> {
+    #[response(status = 200)]
+    Ok(T),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+/// Pushes every event of a group to the client as a json text frame
+#[get("/chat/<group>/live")]
+pub async fn socket(
+    ws: ws::WebSocket,
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<hub::Hub>,
+    group: &str,
+) -> LiveResponse<ws::Channel<'static>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return LiveResponse::Unauthorized(String::new()),
+        None => return LiveResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return LiveResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return LiveResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return LiveResponse::Unauthorized(e);
+    }
+
+    let mut events = hub.subscribe(&group.id);
+    LiveResponse::Ok(ws.channel(move |mut stream| {
+        Box::pin(async move {
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            let text = rocket::serde::json::to_string(&event)
+                                .expect("Failed to serialize event");
+                            stream.send(ws::Message::Text(text)).await?;
+
+                            if event.removes(&session.user) {
+                                break;
+                            }
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Live connection skipped {skipped} events");
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    message = stream.next() => match message {
+                        Some(Ok(ws::Message::Close(_))) | None => break,
+                        Some(Ok(_)) => (), // Clients send messages through `/chat/<group>/send`
+                        Some(Err(e)) => return Err(e),
+                    },
+                }
+            }
+
+            Ok(())
+        })
+    }))
+}
+
+/// Server-Sent Events fallback for clients that can't upgrade to a WebSocket
+///
+/// Only new messages carry an event id, `<created>:<id>` like a
+/// [`cursor`]. When reconnecting with a `Last-Event-ID`, every message after
+/// that cursor is replayed, a page at a time, before live events resume.
+#[get("/chat/<group>/events")]
+pub async fn events(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<hub::Hub>,
+    group: &str,
+    last_event_id: LastEventId,
+    mut shutdown: Shutdown,
+) -> LiveResponse<EventStream![Event + 'static]> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return LiveResponse::Unauthorized(String::new()),
+        None => return LiveResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return LiveResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return LiveResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return LiveResponse::Unauthorized(e);
+    }
+
+    // Subscribe before replaying so nothing slips through in between
+    let mut events = hub.subscribe(&group.id);
+    let database = database.inner().clone();
+
+    LiveResponse::Ok(EventStream! {
+        let mut last_seen = last_event_id.0;
+        if let Some(mut since) = last_seen.clone() {
+            loop {
+                let missed = match database
+                    .get_messages_since(&group.id, since.clone(), cursor::MAX_COUNT)
+                    .await
+                {
+                    Ok(messages) => messages,
+                    Err(e) => {
+                        error!("Database: {e:?}");
+                        return;
+                    }
+                };
+
+                let done = (missed.len() as u64) < cursor::MAX_COUNT;
+                for message in missed {
+                    since = db::Cursor::from(&message);
+                    let event = hub::Event::MessageCreated(Message::from(message));
+                    yield to_sse(&event);
+                }
+                if done {
+                    break;
+                }
+            }
+            last_seen = Some(since);
+        }
+
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Event stream skipped {skipped} events");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            // Already replayed from the database
+            if let (hub::Event::MessageCreated(message), Some(seen)) = (&event, &last_seen) {
+                let message = message_cursor(message);
+                if (message.created, &message.id) <= (seen.created, &seen.id) {
+                    continue;
+                }
+            }
+
+            yield to_sse(&event);
+            if event.removes(&session.user) {
+                break;
+            }
+        }
+    })
+}
+
+fn message_cursor(message: &Message) -> db::Cursor {
+    db::Cursor {
+        created: message.created,
+        id: db::RecordId::from(("message", message.id.as_str())),
+    }
+}
+
+fn to_sse(event: &hub::Event) -> Event {
+    let sse = match event {
+        hub::Event::MessageCreated(message)
+        | hub::Event::MessageUpdated(message)
+        | hub::Event::MessageDeleted(message) => Event::json(message),
+        hub::Event::MemberAdded(change) | hub::Event::MemberRemoved(change) => Event::json(change),
+        hub::Event::ReactionAdded(change) | hub::Event::ReactionRemoved(change) => {
+            Event::json(change)
+        }
+        hub::Event::MessagePinned(change) | hub::Event::MessageUnpinned(change) => {
+            Event::json(change)
+        }
+        hub::Event::GroupDeleted(change) => Event::json(change),
+    };
+
+    // Only new messages move the client's `Last-Event-ID` forward
+    match event {
+        hub::Event::MessageCreated(message) => sse
+            .event(event.name())
+            .id(cursor::encode(&message_cursor(message))),
+        _ => sse.event(event.name()),
+    }
+}
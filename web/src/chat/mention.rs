@@ -0,0 +1,230 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::{
+        cursor,
+        message::Message,
+        permission::{self, Permission},
+    },
+    session,
+};
+
+#[derive(Responder)]
+enum MentionResponse<T> {
+    #[response(status = 200)]
+    Ok(Json<T>),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+#[derive(serde::Serialize)]
+struct Mention {
+    pub id: String,
+    pub group: String,
+    /// The message the user was mentioned in
+    pub message: Message,
+    pub created: i64,
+    pub read: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct MentionPage {
+    pub mentions: Vec<Mention>,
+    /// Pass as `before` (or `after`) to continue, `None` when there is nothing more to load
+    pub next_cursor: Option<String>,
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+/// Finds the `members` mentioned as `@username` in `text`, in order of their
+/// first mention
+///
+/// An `@` directly following a word, like in an email address, isn't a mention.
+pub fn parse(text: &str, members: &[db::RecordId]) -> Vec<db::RecordId> {
+    let mut mentions: Vec<db::RecordId> = Vec::new();
+    for (at, _) in text.match_indices('@') {
+        if text[..at].chars().next_back().is_some_and(is_username_char) {
+            continue;
+        }
+
+        let rest = &text[at + 1..];
+        let end = rest.find(|c| !is_username_char(c)).unwrap_or(rest.len());
+        // Allow a mention to end a sentence
+        let username = rest[..end].trim_end_matches('.');
+        if username.is_empty() {
+            continue;
+        }
+
+        let user = db::RecordId::from(("user", username));
+        if members.contains(&user) && !mentions.contains(&user) {
+            mentions.push(user);
+        }
+    }
+
+    mentions
+}
+
+/// Returns a page of the unread mentions of the user across all their groups,
+/// newest first
+///
+/// Mentions in groups the user can't read anymore, like ones they left, are
+/// left out, so a page can come back short while `next_cursor` goes on.
+#[get("/mentions?<count>&<before>&<after>")]
+pub async fn get(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    count: Option<u64>,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> MentionResponse<MentionPage> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MentionResponse::Unauthorized(String::new()),
+        None => return MentionResponse::InternalServerError(String::new()),
+    };
+
+    let count = count
+        .unwrap_or(cursor::DEFAULT_COUNT)
+        .min(cursor::MAX_COUNT);
+    let page = match cursor::page("mention", before, after) {
+        Ok(page) => page,
+        Err(e) => return MentionResponse::BadRequest(e),
+    };
+
+    let db_mentions = match database
+        .get_unread_mentions(&session.user, page.clone(), count)
+        .await
+    {
+        Ok(mentions) => mentions,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    let next_cursor = cursor::next(&page, &db_mentions, count, |m| db::Cursor::from(m));
+
+    let mut readable: Vec<db::RecordId> = Vec::new();
+    for mention in &db_mentions {
+        if readable.contains(&mention.group) {
+            continue;
+        }
+
+        match database.get_group(&mention.group.key().to_string()).await {
+            Ok(Some(group)) => {
+                if permission::check(&group, &session.user, Permission::Read).is_ok() {
+                    readable.push(group.id);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                error!("Database: {e:?}");
+                return MentionResponse::InternalServerError(String::new());
+            }
+        }
+    }
+    let db_mentions: Vec<db::Mention> = db_mentions
+        .into_iter()
+        .filter(|mention| readable.contains(&mention.group))
+        .collect();
+
+    let ids = db_mentions.iter().map(|m| m.message.clone()).collect();
+    let messages = match database.get_messages_by_ids(ids).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    let mentions: Vec<Mention> = db_mentions
+        .into_iter()
+        .filter_map(|mention| {
+            let message = messages.iter().find(|m| m.id == mention.message)?;
+            Some(Mention {
+                id: mention.id.key().to_string(),
+                group: mention.group.key().to_string(),
+                message: Message::from(message.clone()),
+                created: mention.created,
+                read: mention.read,
+            })
+        })
+        .collect();
+
+    MentionResponse::Ok(Json(MentionPage {
+        mentions,
+        next_cursor,
+    }))
+}
+
+/// Marks a mention as read, removing it from the unread mentions
+#[post("/mentions/<id>/read")]
+pub async fn read(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    id: &str,
+) -> MentionResponse<Mention> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MentionResponse::Unauthorized(String::new()),
+        None => return MentionResponse::InternalServerError(String::new()),
+    };
+
+    let mention = match database.get_mention(id).await {
+        Ok(Some(mention)) if mention.user == session.user => mention,
+        Ok(_) => return MentionResponse::BadRequest("Mention doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    let group = match database.get_group(&mention.group.key().to_string()).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MentionResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return MentionResponse::Unauthorized(e);
+    }
+
+    let message = match database
+        .get_message(&mention.message.key().to_string())
+        .await
+    {
+        Ok(Some(message)) => message,
+        Ok(None) => return MentionResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    let read = chrono::Utc::now().timestamp_millis();
+    let mention = match database.read_mention(mention.id, read).await {
+        Ok(Some(mention)) => mention,
+        Ok(None) => return MentionResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MentionResponse::InternalServerError(String::new());
+        }
+    };
+
+    MentionResponse::Ok(Json(Mention {
+        id: mention.id.key().to_string(),
+        group: mention.group.key().to_string(),
+        message: Message::from(message),
+        created: mention.created,
+        read: mention.read,
+    }))
+}
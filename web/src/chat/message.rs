@@ -1,6 +1,21 @@
 use rocket::{http::CookieJar, serde::json::Json, State};
 
-use crate::session;
+use crate::{
+    chat::{
+        attachment, cursor, mention,
+        permission::{self, Permission},
+    },
+    hub::{Event, Hub, ReactionChange},
+    markdown, session, storage,
+    typing::Typing,
+    user,
+};
+
+/// Which variant of the author's profile picture messages link to
+const AVATAR_SIZE: u32 = 64;
+
+/// Longest emoji accepted as a reaction, in bytes, enough for ZWJ sequences
+const MAX_EMOJI_LEN: usize = 32;
 
 #[derive(Responder)]
 enum MessageResponse<T> {
@@ -14,28 +29,235 @@ enum MessageResponse<T> {
     InternalServerError(String),
 }
 
-#[derive(serde::Serialize)]
-struct Message {
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Message {
     pub id: String,
     pub group: String,
     pub author: String,
+    /// The raw Markdown, as written and as it should be edited
     pub text: String,
+    /// `text` rendered to sanitized HTML
+    pub html: String,
     pub created: i64,
+    pub edited: Option<i64>,
+    /// Deleted messages are kept as tombstones with an empty `text`
+    pub deleted: Option<i64>,
+    /// The top level message of the thread this message belongs to
+    pub reply_to: Option<String>,
+    /// Number of replies in the thread of a top level message
+    pub replies: u64,
+    pub reactions: Vec<Reaction>,
+    /// Usernames of the members mentioned in the message
+    pub mentions: Vec<String>,
+    pub attachments: Vec<attachment::Attachment>,
+    /// URL of the author's profile picture
+    pub avatar: Option<String>,
+    /// Records a change to the group made by `author`, `text` describes the change
+    pub system: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Reaction {
+    pub emoji: String,
+    pub count: u64,
+    /// Whether the requesting user reacted with this emoji, left out of
+    /// messages sent to everyone following the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub me: Option<bool>,
+}
+
+impl Message {
+    /// The message as sent to everyone following the group, without the
+    /// reactions of whoever loaded it
+    fn for_everyone(mut self) -> Self {
+        for reaction in &mut self.reactions {
+            reaction.me = None;
+        }
+        self
+    }
+}
+
+impl From<db::Message> for Message {
+    fn from(msg: db::Message) -> Self {
+        Self {
+            id: msg.id.key().to_string(),
+            group: msg.group.key().to_string(),
+            author: msg.author.key().to_string(),
+            html: markdown::render(&msg.text),
+            text: msg.text,
+            created: msg.created,
+            edited: msg.edited,
+            deleted: msg.deleted,
+            reply_to: msg.reply_to.map(|parent| parent.key().to_string()),
+            replies: 0,
+            reactions: Vec::new(),
+            mentions: msg
+                .mentions
+                .iter()
+                .map(|user| user.key().to_string())
+                .collect(),
+            attachments: Vec::new(),
+            avatar: None,
+            system: msg.system,
+        }
+    }
+}
+
+/// Picks the reactions on `message` out of `counts`, ordered by emoji
+fn reactions_of(counts: &[db::ReactionCount], message: &db::RecordId) -> Vec<Reaction> {
+    let mut reactions: Vec<Reaction> = counts
+        .iter()
+        .filter(|c| &c.message == message)
+        .map(|c| Reaction {
+            emoji: c.emoji.clone(),
+            count: c.count,
+            me: Some(c.mine),
+        })
+        .collect();
+    reactions.sort_by(|a, b| a.emoji.cmp(&b.emoji));
+
+    reactions
+}
+
+/// Picks the attachments of `message` out of `attachments`
+fn attachments_of(
+    attachments: &[db::Attachment],
+    message: &db::RecordId,
+) -> Vec<attachment::Attachment> {
+    attachments
+        .iter()
+        .filter(|a| a.message.as_ref() == Some(message))
+        .cloned()
+        .map(attachment::Attachment::from)
+        .collect()
+}
+
+/// Fills in everything a message shows besides its own record: reply counts,
+/// reactions as seen by `viewer`, attachments and the author's avatar
+pub(crate) async fn load_details(
+    database: &db::Store,
+    viewer: &db::RecordId,
+    db_messages: Vec<db::Message>,
+) -> Result<Vec<Message>, db::Error> {
+    let ids: Vec<db::RecordId> = db_messages.iter().map(|m| m.id.clone()).collect();
+    let reply_counts = database.count_replies(ids.clone()).await?;
+    let reaction_counts = database.count_reactions(ids.clone(), viewer).await?;
+    let attachments = database.get_attachments(ids).await?;
+
+    let mut authors: Vec<db::RecordId> = Vec::new();
+    for msg in &db_messages {
+        if !authors.contains(&msg.author) {
+            authors.push(msg.author.clone());
+        }
+    }
+    let authors = database.get_users(authors).await?;
+
+    Ok(db_messages
+        .into_iter()
+        .map(|msg| {
+            let replies = reply_counts
+                .iter()
+                .find(|c| c.reply_to == msg.id)
+                .map_or(0, |c| c.count);
+            let reactions = reactions_of(&reaction_counts, &msg.id);
+            let attachments = attachments_of(&attachments, &msg.id);
+            let avatar = authors
+                .iter()
+                .find(|u| u.id == msg.author)
+                .and_then(|u| u.avatar.as_deref())
+                .map(|avatar| user::avatar_url(avatar, AVATAR_SIZE));
+
+            Message {
+                replies,
+                reactions,
+                attachments,
+                avatar,
+                ..Message::from(msg)
+            }
+        })
+        .collect())
+}
+
+/// Records a change to `group` made by `author` in its history and sends it to
+/// everyone following the group
+pub(crate) async fn post_system(
+    database: &db::Store,
+    hub: &Hub,
+    group: &db::RecordId,
+    author: &db::RecordId,
+    text: String,
+) -> Result<(), db::Error> {
+    let message = database
+        .create_message(db::CreateMessage {
+            group: group.clone(),
+            author: author.clone(),
+            text,
+            created: chrono::Utc::now().timestamp_millis(),
+            reply_to: None,
+            mentions: Vec::new(),
+            system: true,
+        })
+        .await?;
+    let Some(message) = message else {
+        return Ok(());
+    };
+
+    let mut messages = load_details(database, author, vec![message]).await?;
+    hub.publish(group, Event::MessageCreated(messages.remove(0)));
+
+    Ok(())
+}
+
+/// Accepts short strings of emoji, rejecting plain text and whitespace
+fn is_emoji(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.len() <= MAX_EMOJI_LEN
+        && !emoji.is_ascii()
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+#[derive(serde::Serialize)]
+struct Revision {
+    pub text: String,
+    pub replaced: i64,
+}
+
+#[derive(serde::Serialize)]
+struct MessagePage {
+    pub messages: Vec<Message>,
+    /// Pass as `before` (or `after`) to continue, `None` when there is nothing more to load
+    pub next_cursor: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
 struct CreateMessage<'a> {
-    pub text: &'a str,
+    /// Owned since text with escapes, like line breaks, can't be borrowed from the JSON
+    pub text: String,
+    /// Replies to a message, replies to a reply end up in the same thread
+    pub reply_to: Option<&'a str>,
+    /// Ids of uploaded attachments to send with the message
+    #[serde(default, borrow)]
+    pub attachments: Vec<&'a str>,
+}
+
+#[derive(serde::Deserialize)]
+struct EditMessage {
+    pub text: String,
 }
 
-#[get("/chat/<group>/messages/<count>/<offset>")]
+/// Returns a page of messages, newest first
+///
+/// Without a cursor the newest messages are returned, `before` pages back
+/// through the history and `after` catches up with newer messages.
+#[get("/chat/<group>/messages?<count>&<before>&<after>")]
 pub async fn get(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
     group: &str,
-    count: u64,
-    offset: u64,
-) -> MessageResponse<Vec<Message>> {
+    count: Option<u64>,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> MessageResponse<MessagePage> {
     let session = match session::verify(cookies, database).await {
         Some(Some(session)) => session,
         Some(None) => return MessageResponse::Unauthorized(String::new()),
@@ -51,11 +273,19 @@ pub async fn get(
         }
     };
 
-    if !group.members.contains(&session.user) {
-        return MessageResponse::Unauthorized("You are not in this group".to_string());
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return MessageResponse::Unauthorized(e);
     }
 
-    let db_messages = match database.get_messages(&group.id, count, offset).await {
+    let count = count
+        .unwrap_or(cursor::DEFAULT_COUNT)
+        .min(cursor::MAX_COUNT);
+    let page = match cursor::page("message", before, after) {
+        Ok(page) => page,
+        Err(e) => return MessageResponse::BadRequest(e),
+    };
+
+    let db_messages = match database.get_messages(&group.id, page.clone(), count).await {
         Ok(messages) => messages,
         Err(e) => {
             error!("Database: {e:?}");
@@ -63,24 +293,97 @@ pub async fn get(
         }
     };
 
-    let messages: Vec<Message> = db_messages
-        .into_iter()
-        .map(|msg| Message {
-            id: msg.id.key().to_string(),
-            group: msg.group.key().to_string(),
-            author: msg.author.key().to_string(),
-            text: msg.text,
-            created: msg.created,
-        })
-        .collect();
+    let next_cursor = cursor::next(&page, &db_messages, count, |m| db::Cursor::from(m));
+    let messages = match load_details(database, &session.user, db_messages).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
 
-    MessageResponse::Ok(Json(messages))
+    MessageResponse::Ok(Json(MessagePage {
+        messages,
+        next_cursor,
+    }))
+}
+
+/// Returns a page of replies to a message, newest first
+#[get("/chat/<group>/message/<id>/thread?<count>&<before>&<after>")]
+pub async fn thread(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    id: &str,
+    count: Option<u64>,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> MessageResponse<MessagePage> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let parent = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let count = count
+        .unwrap_or(cursor::DEFAULT_COUNT)
+        .min(cursor::MAX_COUNT);
+    let page = match cursor::page("message", before, after) {
+        Ok(page) => page,
+        Err(e) => return MessageResponse::BadRequest(e),
+    };
+
+    let db_messages = match database.get_thread(&parent.id, page.clone(), count).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let next_cursor = cursor::next(&page, &db_messages, count, |m| db::Cursor::from(m));
+    let messages = match load_details(database, &session.user, db_messages).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    MessageResponse::Ok(Json(MessagePage {
+        messages,
+        next_cursor,
+    }))
 }
 
 #[post("/chat/<group>/send", format = "json", data = "<message>")]
 pub async fn send(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    typing: &State<Typing>,
     group: &str,
     message: Json<CreateMessage<'_>>,
 ) -> MessageResponse<Message> {
@@ -99,17 +402,67 @@ pub async fn send(
         }
     };
 
-    if !group.members.contains(&session.user) {
-        return MessageResponse::Unauthorized("You are not in this group.".to_string());
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return MessageResponse::Unauthorized(e);
     }
 
+    let reply_to = match message.reply_to {
+        Some(parent) => match database.get_message(parent).await {
+            Ok(Some(parent)) if parent.group == group.id && parent.deleted.is_none() => {
+                // Threads are only one level deep
+                Some(parent.reply_to.unwrap_or(parent.id))
+            }
+            Ok(_) => {
+                return MessageResponse::BadRequest(
+                    "The message you're replying to doesn't exist.".to_string(),
+                )
+            }
+            Err(e) => {
+                error!("Database: {e:?}");
+                return MessageResponse::InternalServerError(String::new());
+            }
+        },
+        None => None,
+    };
+
+    if message.attachments.len() > attachment::MAX_ATTACHMENTS {
+        return MessageResponse::BadRequest(format!(
+            "A message can have at most {} attachments.",
+            attachment::MAX_ATTACHMENTS
+        ));
+    }
+
+    let mut attachments = Vec::with_capacity(message.attachments.len());
+    for id in &message.attachments {
+        match database.get_attachment(id).await {
+            Ok(Some(attachment))
+                if attachment.group == group.id
+                    && attachment.uploader == session.user
+                    && attachment.message.is_none() =>
+            {
+                attachments.push(attachment.id)
+            }
+            Ok(_) => {
+                return MessageResponse::BadRequest(format!("Attachment `{id}` doesn't exist."))
+            }
+            Err(e) => {
+                error!("Database: {e:?}");
+                return MessageResponse::InternalServerError(String::new());
+            }
+        }
+    }
+
+    let mentions = mention::parse(&message.text, &group.members);
     let created = chrono::Utc::now().timestamp_millis();
     let message = match database
         .create_message(db::CreateMessage {
-            group: group.id,
-            author: session.user,
-            text: message.text.to_string(),
+            group: group.id.clone(),
+            author: session.user.clone(),
+            text: message.text.clone(),
             created,
+            reply_to,
+            mentions: mentions.clone(),
+            system: false,
         })
         .await
     {
@@ -123,11 +476,425 @@ pub async fn send(
         }
     };
 
-    MessageResponse::Ok(Json(Message {
-        id: message.id.key().to_string(),
-        group: message.group.key().to_string(),
-        author: message.author.key().to_string(),
-        text: message.text,
-        created: message.created,
-    }))
+    if !attachments.is_empty() {
+        if let Err(e) = database
+            .attach_to_message(attachments, message.id.clone())
+            .await
+        {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    }
+
+    // Mentioning yourself doesn't need a notification
+    let inbox: Vec<db::CreateMention> = mentions
+        .into_iter()
+        .filter(|user| user != &session.user)
+        .map(|user| db::CreateMention {
+            user,
+            message: message.id.clone(),
+            group: group.id.clone(),
+            created,
+        })
+        .collect();
+    if !inbox.is_empty() {
+        if let Err(e) = database.create_mentions(inbox).await {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    }
+
+    let message = match load_details(database, &session.user, vec![message]).await {
+        Ok(mut messages) => messages.remove(0),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+    typing.stop(&group.id, &session.user);
+    hub.publish(&group.id, Event::MessageCreated(message.clone()));
+
+    MessageResponse::Ok(Json(message))
+}
+
+/// Replaces the text of a message
+///
+/// Besides the author, members with [`Permission::EditOthers`] may edit any message.
+#[patch("/chat/<group>/message/<id>", format = "json", data = "<edit>")]
+pub async fn edit(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+    edit: Json<EditMessage>,
+) -> MessageResponse<Message> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if message.author != session.user {
+        if let Err(e) = permission::check(&group, &session.user, Permission::EditOthers) {
+            return MessageResponse::Unauthorized(e);
+        }
+    }
+
+    if message.system {
+        return MessageResponse::BadRequest("System messages can't be edited.".to_string());
+    }
+
+    if edit.text.is_empty() {
+        return MessageResponse::BadRequest("A message can't be empty.".to_string());
+    }
+
+    let mentions = mention::parse(&edit.text, &group.members);
+    let edited = chrono::Utc::now().timestamp_millis();
+    let message = match database
+        .edit_message(message.id, edit.text.clone(), mentions, edited)
+        .await
+    {
+        Ok(Some(message)) => message,
+        Ok(None) => return MessageResponse::BadRequest("Message has been deleted.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let message = match load_details(database, &session.user, vec![message]).await {
+        Ok(mut messages) => messages.remove(0),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+    hub.publish(
+        &group.id,
+        Event::MessageUpdated(message.clone().for_everyone()),
+    );
+
+    MessageResponse::Ok(Json(message))
+}
+
+/// Deletes a message, leaving a tombstone in its place
+///
+/// Besides the author, members with [`Permission::DeleteOthers`] may delete any message.
+#[delete("/chat/<group>/message/<id>")]
+pub async fn delete(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+) -> MessageResponse<Message> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if message.author != session.user {
+        if let Err(e) = permission::check(&group, &session.user, Permission::DeleteOthers) {
+            return MessageResponse::Unauthorized(e);
+        }
+    }
+
+    if message.deleted.is_some() {
+        return MessageResponse::BadRequest("Message has already been deleted.".to_string());
+    }
+
+    let attachments = match database.get_attachments(vec![message.id.clone()]).await {
+        Ok(attachments) => attachments,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let deleted = chrono::Utc::now().timestamp_millis();
+    let message = match database.delete_message(message.id, deleted).await {
+        Ok(Some(message)) => message,
+        Ok(None) => return MessageResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    for attachment in attachments {
+        if let Err(e) = blobs.delete(&attachment.id.key().to_string()).await {
+            error!("Storage: {e:?}");
+        }
+    }
+
+    let message = Message::from(message);
+    hub.publish(&group.id, Event::MessageDeleted(message.clone()));
+
+    MessageResponse::Ok(Json(message))
+}
+
+/// Reacts to a message with an emoji, returns the reactions on the message
+#[put("/chat/<group>/message/<id>/reaction/<emoji>")]
+pub async fn react(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+    emoji: &str,
+) -> MessageResponse<Vec<Reaction>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if message.deleted.is_some() {
+        return MessageResponse::BadRequest("Message has been deleted.".to_string());
+    }
+
+    if !is_emoji(emoji) {
+        return MessageResponse::BadRequest("Reactions have to be emoji.".to_string());
+    }
+
+    let created = chrono::Utc::now().timestamp_millis();
+    let added = match database
+        .add_reaction(db::CreateReaction {
+            message: message.id.clone(),
+            user: session.user.clone(),
+            emoji: emoji.to_string(),
+            created,
+        })
+        .await
+    {
+        Ok(added) => added.is_some(),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let reactions = match database
+        .count_reactions(vec![message.id.clone()], &session.user)
+        .await
+    {
+        Ok(counts) => reactions_of(&counts, &message.id),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    // Reacting twice with the same emoji changes nothing
+    if added {
+        let count = reactions
+            .iter()
+            .find(|r| r.emoji == emoji)
+            .map_or(0, |r| r.count);
+        let change = ReactionChange {
+            message: message.id.key().to_string(),
+            user: session.user.key().to_string(),
+            emoji: emoji.to_string(),
+            count,
+            created,
+        };
+        hub.publish(&group.id, Event::ReactionAdded(change));
+    }
+
+    MessageResponse::Ok(Json(reactions))
+}
+
+/// Takes back a reaction, returns the reactions on the message
+#[delete("/chat/<group>/message/<id>/reaction/<emoji>")]
+pub async fn unreact(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+    emoji: &str,
+) -> MessageResponse<Vec<Reaction>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let removed = match database
+        .remove_reaction(message.id.clone(), session.user.clone(), emoji.to_string())
+        .await
+    {
+        Ok(removed) => removed.is_some(),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let reactions = match database
+        .count_reactions(vec![message.id.clone()], &session.user)
+        .await
+    {
+        Ok(counts) => reactions_of(&counts, &message.id),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if removed {
+        let count = reactions
+            .iter()
+            .find(|r| r.emoji == emoji)
+            .map_or(0, |r| r.count);
+        let change = ReactionChange {
+            message: message.id.key().to_string(),
+            user: session.user.key().to_string(),
+            emoji: emoji.to_string(),
+            count,
+            created: chrono::Utc::now().timestamp_millis(),
+        };
+        hub.publish(&group.id, Event::ReactionRemoved(change));
+    }
+
+    MessageResponse::Ok(Json(reactions))
+}
+
+/// Returns the previous texts of an edited message, oldest first
+#[get("/chat/<group>/message/<id>/revisions")]
+pub async fn revisions(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    id: &str,
+) -> MessageResponse<Vec<Revision>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return MessageResponse::Unauthorized(String::new()),
+        None => return MessageResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return MessageResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return MessageResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return MessageResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return MessageResponse::InternalServerError(String::new());
+        }
+    };
+
+    let revisions: Vec<Revision> = message
+        .revisions
+        .into_iter()
+        .map(|revision| Revision {
+            text: revision.text,
+            replaced: revision.replaced,
+        })
+        .collect();
+
+    MessageResponse::Ok(Json(revisions))
 }
@@ -0,0 +1,229 @@
+//! What each [`db::Role`] is allowed to do in a group
+
+/// Something a member may be allowed to do in a group
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Read messages and follow the group live
+    Read,
+    /// Send, edit and react to messages and upload attachments
+    Send,
+    Invite,
+    Remove,
+    Pin,
+    /// Edit messages written by other members
+    EditOthers,
+    /// Delete messages written by other members
+    DeleteOthers,
+    /// Change the name, description, topic and icon
+    EditGroup,
+    /// Change the roles of other members
+    ManageRoles,
+    /// Hand the group over to another member, making them the owner
+    Transfer,
+    /// Delete the group, until it's purged it can be restored
+    Delete,
+}
+
+impl Permission {
+    fn granted_to(self, role: db::Role) -> bool {
+        match role {
+            db::Role::Owner => true,
+            db::Role::Admin => !matches!(
+                self,
+                Permission::EditOthers
+                    | Permission::ManageRoles
+                    | Permission::Transfer
+                    | Permission::Delete
+            ),
+            db::Role::Member => matches!(self, Permission::Read | Permission::Send),
+            db::Role::ReadOnly => self == Permission::Read,
+        }
+    }
+
+    /// Whether the permission can be granted in direct messages at all,
+    /// those stay between their two users
+    fn allowed_in_direct(self) -> bool {
+        matches!(self, Permission::Read | Permission::Send | Permission::Pin)
+    }
+
+    fn action(self) -> &'static str {
+        match self {
+            Permission::Read => "read this group",
+            Permission::Send => "send messages in this group",
+            Permission::Invite => "add members",
+            Permission::Remove => "remove members",
+            Permission::Pin => "pin messages",
+            Permission::EditOthers => "edit messages of other members",
+            Permission::DeleteOthers => "delete messages of other members",
+            Permission::EditGroup => "change the settings of this group",
+            Permission::ManageRoles => "change roles",
+            Permission::Transfer => "transfer this group",
+            Permission::Delete => "delete this group",
+        }
+    }
+}
+
+/// Checks that `user` is a member of `group` whose role grants `permission`
+///
+/// In direct messages only reading, sending and pinning are possible, for both
+/// users alike whatever their role.
+///
+/// Returns the role of the user, or the reason to reject the request with.
+pub fn check(
+    group: &db::Group,
+    user: &db::RecordId,
+    permission: Permission,
+) -> Result<db::Role, String> {
+    let Some(role) = group.role_of(user) else {
+        return Err("You are not in this group.".to_string());
+    };
+
+    if group.direct.is_some() {
+        if !permission.allowed_in_direct() {
+            return Err(format!(
+                "You can't {} in direct messages.",
+                permission.action()
+            ));
+        }
+
+        // The user who opened the direct messages owns them, the other one
+        // mustn't be able to do any less
+        return Ok(role);
+    }
+
+    if !permission.granted_to(role) {
+        return Err(format!(
+            "Your role doesn't allow you to {}.",
+            permission.action()
+        ));
+    }
+
+    Ok(role)
+}
+
+#[cfg(test)]
+mod tests {
+    use db::{RecordId, Role};
+
+    use super::*;
+
+    const ALL: [Permission; 11] = [
+        Permission::Read,
+        Permission::Send,
+        Permission::Invite,
+        Permission::Remove,
+        Permission::Pin,
+        Permission::EditOthers,
+        Permission::DeleteOthers,
+        Permission::EditGroup,
+        Permission::ManageRoles,
+        Permission::Transfer,
+        Permission::Delete,
+    ];
+
+    fn user(name: &str) -> RecordId {
+        RecordId::from(("user", name))
+    }
+
+    /// A group owned by `owner` with the admin `admin`, the member `member`
+    /// and the read-only member `reader`
+    fn group() -> db::Group {
+        db::Group {
+            id: RecordId::from(("group", "general")),
+            owner: user("owner"),
+            name: "general".to_string(),
+            members: ["owner", "admin", "member", "reader"].map(user).to_vec(),
+            created: 0,
+            pinned: Vec::new(),
+            admins: vec![user("admin")],
+            read_only: vec![user("reader")],
+            direct: None,
+            deleted: None,
+            description: None,
+            topic: None,
+            icon: None,
+        }
+    }
+
+    /// The permissions `name` is granted in `group`
+    fn granted(group: &db::Group, name: &str) -> Vec<&'static str> {
+        ALL.into_iter()
+            .filter(|permission| check(group, &user(name), *permission).is_ok())
+            .map(Permission::action)
+            .collect()
+    }
+
+    #[test]
+    fn owner_may_do_anything() {
+        assert_eq!(granted(&group(), "owner").len(), ALL.len());
+        assert!(check(&group(), &user("owner"), Permission::Read) == Ok(Role::Owner));
+    }
+
+    #[test]
+    fn admins_cant_change_roles_transfer_or_delete_the_group() {
+        let group = group();
+        for permission in [
+            Permission::EditOthers,
+            Permission::ManageRoles,
+            Permission::Transfer,
+            Permission::Delete,
+        ] {
+            assert!(
+                !permission.granted_to(Role::Admin),
+                "{}",
+                permission.action()
+            );
+            assert!(check(&group, &user("admin"), permission).is_err());
+        }
+        assert_eq!(granted(&group, "admin").len(), ALL.len() - 4);
+    }
+
+    #[test]
+    fn members_only_read_and_send() {
+        assert_eq!(
+            granted(&group(), "member"),
+            [Permission::Read.action(), Permission::Send.action()]
+        );
+        assert!(check(&group(), &user("member"), Permission::Pin).is_err());
+    }
+
+    #[test]
+    fn read_only_members_cant_send() {
+        assert_eq!(granted(&group(), "reader"), [Permission::Read.action()]);
+        assert_eq!(
+            check(&group(), &user("reader"), Permission::Send).err(),
+            Some("Your role doesn't allow you to send messages in this group.".to_string())
+        );
+    }
+
+    #[test]
+    fn outsiders_may_do_nothing() {
+        assert!(granted(&group(), "stranger").is_empty());
+        assert_eq!(
+            check(&group(), &user("stranger"), Permission::Read).err(),
+            Some("You are not in this group.".to_string())
+        );
+    }
+
+    #[test]
+    fn direct_messages_only_allow_reading_sending_and_pinning() {
+        let direct = db::Group {
+            members: vec![user("owner"), user("member")],
+            admins: Vec::new(),
+            read_only: Vec::new(),
+            direct: Some(db::Group::direct_key(&user("owner"), &user("member"))),
+            ..group()
+        };
+        let allowed = [
+            Permission::Read.action(),
+            Permission::Send.action(),
+            Permission::Pin.action(),
+        ];
+        assert_eq!(granted(&direct, "owner"), allowed);
+        assert_eq!(granted(&direct, "member"), allowed);
+        assert_eq!(
+            check(&direct, &user("owner"), Permission::Delete).err(),
+            Some("You can't delete this group in direct messages.".to_string())
+        );
+    }
+}
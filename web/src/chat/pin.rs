@@ -0,0 +1,217 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::{
+        message::{self, Message},
+        permission::{self, Permission},
+    },
+    hub::{Event, Hub, PinChange},
+    session,
+};
+
+/// Most messages a group can have pinned at once
+const MAX_PINS: usize = 50;
+
+#[derive(Responder)]
+enum PinResponse<T> {
+    #[response(status = 200)]
+    Ok(Json<T>),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+/// Ids of the pinned messages of `group`, most recently pinned first
+fn pinned_ids(group: &db::Group) -> Vec<String> {
+    group
+        .pinned
+        .iter()
+        .rev()
+        .map(|message| message.key().to_string())
+        .collect()
+}
+
+/// Returns the pinned messages of a group, most recently pinned first
+#[get("/chat/<group>/pinned")]
+pub async fn get(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+) -> PinResponse<Vec<Message>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return PinResponse::Unauthorized(String::new()),
+        None => return PinResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return PinResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return PinResponse::Unauthorized(e);
+    }
+
+    let ids = group.pinned.into_iter().rev().collect();
+    let db_messages = match database.get_messages_by_ids(ids).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    let messages = match message::load_details(database, &session.user, db_messages).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    PinResponse::Ok(Json(messages))
+}
+
+/// Pins a message, returns the ids of the pinned messages
+///
+/// Requires [`Permission::Pin`].
+#[put("/chat/<group>/message/<id>/pin")]
+pub async fn pin(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+) -> PinResponse<Vec<String>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return PinResponse::Unauthorized(String::new()),
+        None => return PinResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return PinResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Pin) {
+        return PinResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return PinResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    if message.deleted.is_some() {
+        return PinResponse::BadRequest("Message has been deleted.".to_string());
+    }
+
+    // Pinning twice changes nothing
+    if group.pinned.contains(&message.id) {
+        return PinResponse::Ok(Json(pinned_ids(&group)));
+    }
+
+    if group.pinned.len() >= MAX_PINS {
+        return PinResponse::BadRequest(format!(
+            "A group can have at most {MAX_PINS} pinned messages."
+        ));
+    }
+
+    let group = match database
+        .pin_message(group.id, message.id.clone(), MAX_PINS)
+        .await
+    {
+        Ok(Some(group)) => group,
+        // Someone else pinned at the same time
+        Ok(None) => {
+            return PinResponse::BadRequest(
+                "The message is already pinned or the group has too many pinned messages."
+                    .to_string(),
+            )
+        }
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    let change = PinChange {
+        message: message.id.key().to_string(),
+        user: session.user.key().to_string(),
+        created: chrono::Utc::now().timestamp_millis(),
+    };
+    hub.publish(&group.id, Event::MessagePinned(change));
+
+    PinResponse::Ok(Json(pinned_ids(&group)))
+}
+
+/// Unpins a message, returns the ids of the pinned messages
+///
+/// Requires [`Permission::Pin`].
+#[delete("/chat/<group>/message/<id>/pin")]
+pub async fn unpin(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    hub: &State<Hub>,
+    group: &str,
+    id: &str,
+) -> PinResponse<Vec<String>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return PinResponse::Unauthorized(String::new()),
+        None => return PinResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return PinResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Pin) {
+        return PinResponse::Unauthorized(e);
+    }
+
+    let message = db::RecordId::from(("message", id));
+    if !group.pinned.contains(&message) {
+        return PinResponse::Ok(Json(pinned_ids(&group)));
+    }
+
+    let group = match database.unpin_message(group.id, message.clone()).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return PinResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return PinResponse::InternalServerError(String::new());
+        }
+    };
+
+    let change = PinChange {
+        message: message.key().to_string(),
+        user: session.user.key().to_string(),
+        created: chrono::Utc::now().timestamp_millis(),
+    };
+    hub.publish(&group.id, Event::MessageUnpinned(change));
+
+    PinResponse::Ok(Json(pinned_ids(&group)))
+}
@@ -0,0 +1,171 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::permission::{self, Permission},
+    session,
+};
+
+/// Groups with more members don't get "seen by" lists, they would be too long
+/// to be useful
+const SEEN_BY_MAX_MEMBERS: usize = 20;
+
+#[derive(Responder)]
+enum ReadResponse<T> {
+    #[response(status = 200)]
+    Ok(Json<T>),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+#[derive(serde::Serialize)]
+struct ReadMarker {
+    pub group: String,
+    /// The newest message the user has read
+    pub message: String,
+    pub read: i64,
+}
+
+impl From<db::ReadMarker> for ReadMarker {
+    fn from(marker: db::ReadMarker) -> Self {
+        Self {
+            group: marker.group.key().to_string(),
+            message: marker.message.key().to_string(),
+            read: marker.read,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct MarkRead<'a> {
+    pub message: &'a str,
+}
+
+/// Marks every message up to and including `message` as read
+///
+/// Marking an older message than the last one read changes nothing, the
+/// current marker is returned either way.
+#[post("/chat/<group>/read", format = "json", data = "<mark>")]
+pub async fn mark(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    mark: Json<MarkRead<'_>>,
+) -> ReadResponse<ReadMarker> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return ReadResponse::Unauthorized(String::new()),
+        None => return ReadResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return ReadResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return ReadResponse::Unauthorized(e);
+    }
+
+    let message = match database.get_message(mark.message).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return ReadResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    let marker = match database
+        .mark_read(db::CreateReadMarker {
+            group: group.id,
+            user: session.user,
+            message: message.id,
+            message_created: message.created,
+            read: chrono::Utc::now().timestamp_millis(),
+        })
+        .await
+    {
+        Ok(Some(marker)) => marker,
+        Ok(None) => return ReadResponse::InternalServerError(String::new()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    ReadResponse::Ok(Json(ReadMarker::from(marker)))
+}
+
+/// Returns the usernames of the members who have read a message, besides its author
+///
+/// Only available in groups of up to [`SEEN_BY_MAX_MEMBERS`] members.
+#[get("/chat/<group>/message/<id>/seen")]
+pub async fn seen_by(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    id: &str,
+) -> ReadResponse<Vec<String>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return ReadResponse::Unauthorized(String::new()),
+        None => return ReadResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return ReadResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return ReadResponse::Unauthorized(e);
+    }
+
+    if group.members.len() > SEEN_BY_MAX_MEMBERS {
+        return ReadResponse::BadRequest(format!(
+            "Only groups of up to {SEEN_BY_MAX_MEMBERS} members show who has seen a message."
+        ));
+    }
+
+    let message = match database.get_message(id).await {
+        Ok(Some(message)) if message.group == group.id => message,
+        Ok(_) => return ReadResponse::BadRequest("Message doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    let markers = match database.get_read_markers(&group.id).await {
+        Ok(markers) => markers,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return ReadResponse::InternalServerError(String::new());
+        }
+    };
+
+    // Markers of former members are kept, they aren't shown
+    let seen_by = markers
+        .into_iter()
+        .filter(|marker| {
+            marker.user != message.author
+                && group.members.contains(&marker.user)
+                && marker.has_read(&message)
+        })
+        .map(|marker| marker.user.key().to_string())
+        .collect();
+
+    ReadResponse::Ok(Json(seen_by))
+}
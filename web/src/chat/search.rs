@@ -0,0 +1,220 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::{
+        cursor,
+        message::{self, Message},
+        permission::{self, Permission},
+    },
+    session,
+};
+
+/// Longest accepted search query, in bytes
+const MAX_QUERY_LEN: usize = 256;
+/// Characters of context kept in front of the first match of a snippet
+const SNIPPET_LEAD: usize = 40;
+/// Longest snippet, in characters of the message text
+const SNIPPET_LEN: usize = 160;
+
+#[derive(Responder)]
+enum SearchResponse<T> {
+    #[response(status = 200)]
+    Ok(Json<T>),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+/// Query parameters shared by both search routes
+#[derive(FromForm)]
+struct SearchQuery<'a> {
+    /// Words which all have to appear in a message
+    q: Option<&'a str>,
+    /// Username of the author
+    author: Option<&'a str>,
+    /// Only messages created at or after this time, in milliseconds
+    since: Option<i64>,
+    /// Only messages created before this time, in milliseconds
+    until: Option<i64>,
+    count: Option<u64>,
+    before: Option<&'a str>,
+    after: Option<&'a str>,
+}
+
+#[derive(serde::Serialize)]
+struct SearchResult {
+    pub message: Message,
+    /// The part of the text around the matches, as HTML with every match in a `<mark>`
+    pub snippet: String,
+}
+
+#[derive(serde::Serialize)]
+struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Pass as `before` (or `after`) to continue, `None` when there is nothing more to load
+    pub next_cursor: Option<String>,
+}
+
+fn escape_into(html: &mut String, text: &[char]) {
+    for &c in text {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// Cuts the part around the first match out of `text`, escaping it and
+/// wrapping each match in a `<mark>`
+fn snippet(text: &str, highlights: &[db::Highlight]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let first = highlights.first().map_or(0, |h| h.start);
+    let start = first.saturating_sub(SNIPPET_LEAD).min(chars.len());
+    let end = (start + SNIPPET_LEN).min(chars.len());
+
+    let mut html = String::new();
+    if start > 0 {
+        html.push('…');
+    }
+
+    let mut position = start;
+    for highlight in highlights {
+        let mark_start = highlight.start.max(position);
+        let mark_end = highlight.end.min(end);
+        if mark_start >= mark_end {
+            continue;
+        }
+
+        escape_into(&mut html, &chars[position..mark_start]);
+        html.push_str("<mark>");
+        escape_into(&mut html, &chars[mark_start..mark_end]);
+        html.push_str("</mark>");
+        position = mark_end;
+    }
+    escape_into(&mut html, &chars[position..end]);
+
+    if end < chars.len() {
+        html.push('…');
+    }
+
+    html
+}
+
+/// Runs a search for `query`, limited to `group` when given
+async fn search(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: Option<&str>,
+    query: SearchQuery<'_>,
+) -> SearchResponse<SearchPage> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return SearchResponse::Unauthorized(String::new()),
+        None => return SearchResponse::InternalServerError(String::new()),
+    };
+
+    let text = query.q.unwrap_or_default().trim();
+    if text.is_empty() {
+        return SearchResponse::BadRequest("Search query is empty.".to_string());
+    }
+    if text.len() > MAX_QUERY_LEN {
+        return SearchResponse::BadRequest(format!(
+            "Search query is longer than {MAX_QUERY_LEN} bytes."
+        ));
+    }
+
+    let group = match group {
+        Some(group) => match database.get_group(group).await {
+            Ok(Some(group)) => match permission::check(&group, &session.user, Permission::Read) {
+                Ok(_) => Some(group.id),
+                Err(e) => return SearchResponse::Unauthorized(e),
+            },
+            Ok(None) => return SearchResponse::BadRequest("Group doesn't exist".to_string()),
+            Err(e) => {
+                error!("Database: {e:?}");
+                return SearchResponse::InternalServerError(String::new());
+            }
+        },
+        None => None,
+    };
+
+    let count = query
+        .count
+        .unwrap_or(cursor::DEFAULT_COUNT)
+        .min(cursor::MAX_COUNT);
+    let page = match cursor::page("message", query.before, query.after) {
+        Ok(page) => page,
+        Err(e) => return SearchResponse::BadRequest(e),
+    };
+
+    let search = db::Search {
+        query: text.to_string(),
+        member: session.user.clone(),
+        group,
+        author: query
+            .author
+            .map(|author| db::RecordId::from(("user", author))),
+        since: query.since,
+        until: query.until,
+    };
+    let hits = match database.search_messages(search, page.clone(), count).await {
+        Ok(hits) => hits,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return SearchResponse::InternalServerError(String::new());
+        }
+    };
+
+    let next_cursor = cursor::next(&page, &hits, count, |h| db::Cursor::from(h));
+    let snippets: Vec<String> = hits
+        .iter()
+        .map(|hit| snippet(&hit.message.text, &hit.highlights))
+        .collect();
+    let db_messages = hits.into_iter().map(|hit| hit.message).collect();
+    let messages = match message::load_details(database, &session.user, db_messages).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Database: {e:?}");
+            return SearchResponse::InternalServerError(String::new());
+        }
+    };
+
+    let results = messages
+        .into_iter()
+        .zip(snippets)
+        .map(|(message, snippet)| SearchResult { message, snippet })
+        .collect();
+
+    SearchResponse::Ok(Json(SearchPage {
+        results,
+        next_cursor,
+    }))
+}
+
+/// Searches the messages of every group the user is in, newest first
+#[get("/chat/search?<query..>")]
+pub async fn all(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    query: SearchQuery<'_>,
+) -> SearchResponse<SearchPage> {
+    search(cookies, database, None, query).await
+}
+
+/// Searches the messages of one group, newest first
+#[get("/chat/<group>/search?<query..>")]
+pub async fn in_group(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    group: &str,
+    query: SearchQuery<'_>,
+) -> SearchResponse<SearchPage> {
+    search(cookies, database, Some(group), query).await
+}
@@ -0,0 +1,90 @@
+use rocket::{http::CookieJar, serde::json::Json, State};
+
+use crate::{
+    chat::permission::{self, Permission},
+    session,
+    typing::Typing,
+};
+
+#[derive(Responder)]
+enum TypingResponse<T> {
+    #[response(status = 200)]
+    Ok(T),
+    #[response(status = 400)]
+    BadRequest(String),
+    #[response(status = 401)]
+    Unauthorized(String),
+    #[response(status = 500)]
+    InternalServerError(String),
+}
+
+/// Signals that the user is composing a message, repeated while they keep typing
+#[post("/chat/<group>/typing")]
+pub async fn start(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    typing: &State<Typing>,
+    group: &str,
+) -> TypingResponse<()> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return TypingResponse::Unauthorized(String::new()),
+        None => return TypingResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return TypingResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return TypingResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Send) {
+        return TypingResponse::Unauthorized(e);
+    }
+
+    typing.start(&group.id, &session.user);
+    TypingResponse::Ok(())
+}
+
+/// Returns the usernames of the other members who are typing right now
+///
+/// Cheap enough to be polled every few seconds, nothing but the group is
+/// loaded from the database.
+#[get("/chat/<group>/typing")]
+pub async fn get(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    typing: &State<Typing>,
+    group: &str,
+) -> TypingResponse<Json<Vec<String>>> {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return TypingResponse::Unauthorized(String::new()),
+        None => return TypingResponse::InternalServerError(String::new()),
+    };
+
+    let group = match database.get_group(group).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return TypingResponse::BadRequest("Group doesn't exist.".to_string()),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return TypingResponse::InternalServerError(String::new());
+        }
+    };
+
+    if let Err(e) = permission::check(&group, &session.user, Permission::Read) {
+        return TypingResponse::Unauthorized(e);
+    }
+
+    let user = session.user.key().to_string();
+    let others = typing
+        .typing(&group.id)
+        .into_iter()
+        .filter(|typing| typing != &user)
+        .collect();
+
+    TypingResponse::Ok(Json(others))
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use rocket::tokio::sync::broadcast;
+
+use crate::chat::message::Message;
+
+/// How many events a slow connection may fall behind before it starts skipping
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "event", content = "data")]
+pub enum Event {
+    #[serde(rename = "message.created")]
+    MessageCreated(Message),
+    #[serde(rename = "message.updated")]
+    MessageUpdated(Message),
+    #[serde(rename = "message.deleted")]
+    MessageDeleted(Message),
+    #[serde(rename = "member.added")]
+    MemberAdded(MemberChange),
+    #[serde(rename = "member.removed")]
+    MemberRemoved(MemberChange),
+    #[serde(rename = "reaction.added")]
+    ReactionAdded(ReactionChange),
+    #[serde(rename = "reaction.removed")]
+    ReactionRemoved(ReactionChange),
+    #[serde(rename = "message.pinned")]
+    MessagePinned(PinChange),
+    #[serde(rename = "message.unpinned")]
+    MessageUnpinned(PinChange),
+    /// Ends every live connection to the group
+    #[serde(rename = "group.deleted")]
+    GroupDeleted(GroupChange),
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct MemberChange {
+    pub group: String,
+    pub member: String,
+    pub created: i64,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct ReactionChange {
+    pub message: String,
+    pub user: String,
+    pub emoji: String,
+    /// How many members reacted with `emoji` after the change
+    pub count: u64,
+    pub created: i64,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct PinChange {
+    pub message: String,
+    /// Who pinned or unpinned the message
+    pub user: String,
+    pub created: i64,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct GroupChange {
+    pub group: String,
+    /// Who changed the group
+    pub user: String,
+    pub created: i64,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::MessageCreated(_) => "message.created",
+            Event::MessageUpdated(_) => "message.updated",
+            Event::MessageDeleted(_) => "message.deleted",
+            Event::MemberAdded(_) => "member.added",
+            Event::MemberRemoved(_) => "member.removed",
+            Event::ReactionAdded(_) => "reaction.added",
+            Event::ReactionRemoved(_) => "reaction.removed",
+            Event::MessagePinned(_) => "message.pinned",
+            Event::MessageUnpinned(_) => "message.unpinned",
+            Event::GroupDeleted(_) => "group.deleted",
+        }
+    }
+
+    /// Returns `true` when this event removes `user` from the group, deleting
+    /// the group removes everyone
+    pub fn removes(&self, user: &db::RecordId) -> bool {
+        match self {
+            Event::MemberRemoved(change) => change.member == user.key().to_string(),
+            Event::GroupDeleted(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Fans out group activity to every live connection of that group
+///
+/// Channels are keyed by the group's `RecordId` and only exist while
+/// someone is subscribed to them.
+#[derive(Default)]
+pub struct Hub {
+    groups: Mutex<HashMap<String, broadcast::Sender<Event>>>,
+}
+
+impl Hub {
+    pub fn subscribe(&self, group: &db::RecordId) -> broadcast::Receiver<Event> {
+        let mut groups = self.groups.lock().unwrap();
+        groups
+            .entry(group.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, group: &db::RecordId, event: Event) {
+        let mut groups = self.groups.lock().unwrap();
+        let key = group.to_string();
+        let Some(sender) = groups.get(&key) else {
+            return; // Nobody is listening
+        };
+
+        if sender.send(event).is_err() {
+            // Every receiver has been dropped
+            groups.remove(&key);
+        }
+    }
+}
@@ -1,29 +1,102 @@
 mod chat;
+mod hub;
+mod markdown;
+mod picture;
+mod purge;
 mod session;
+mod storage;
+mod typing;
 mod user;
 
+use std::sync::Arc;
+
 use rocket::{
+    figment::{
+        providers::{Env, Serialized},
+        Figment,
+    },
     http::{ContentType, CookieJar},
     response::Redirect,
+    Build, Rocket,
 };
 
 #[macro_use]
 extern crate rocket;
 
+/// Room for the other fields of a multipart upload next to the file itself
+const FORM_OVERHEAD: u64 = 64 * 1024;
+
 #[launch]
 #[tokio::main]
 async fn rocket() -> _ {
-    let db_address =
-        std::env::var("DB_ADDRESS").expect("`DB_ADDRESS` environment variable not provided");
-    // let db_token_path =
-    //     std::env::var("DB_TOKEN_PATH").expect("`DB_TOKEN` environment variable not provided");
-    // let db_token = std::fs::read_to_string(db_token_path)
-    //     .expect("couldn't read 'Json Web Token' from File at '{db_token_path}'");
+    // `DB_*` environment variables override the `database` table of `Rocket.toml`
+    let figment = rocket::Config::figment()
+        .merge(
+            Env::prefixed("DB_")
+                .map(|key| format!("database.{key}").into())
+                .global(),
+        )
+        .join(Serialized::default("storage", storage::Config::default()));
+    let config: db::Config = figment
+        .extract_inner("database")
+        .expect("Invalid or missing database configuration");
+    let storage: storage::Config = figment
+        .extract_inner("storage")
+        .expect("Invalid storage configuration");
+
+    let db = db::DBConnection::new(&config).await.unwrap();
 
-    let db = db::DBConnection::new(db_address).await.unwrap();
-    db.prepare().await;
+    // `chatter_web migrate` only applies pending migrations
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        match db.migrate().await {
+            Ok(applied) => {
+                println!("Applied {applied} migration(s)");
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Migration failed: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if config.auto_migrate {
+        let applied = db.migrate().await.expect("Failed to migrate database");
+        info!("Applied {applied} migration(s)");
+    } else {
+        let pending = db
+            .pending_migrations()
+            .await
+            .expect("Failed to check database migrations");
+        if !pending.is_empty() {
+            panic!(
+                "{} migration(s) haven't been applied, run `chatter_web migrate` first",
+                pending.len()
+            );
+        }
+    }
 
-    rocket::build()
+    let blobs =
+        storage::LocalStorage::new(&storage.path).expect("Failed to create the upload directory");
+
+    build(figment, Arc::new(db), Arc::new(blobs), storage)
+}
+
+/// Builds the `chatter` server on top of `store` and `blobs`
+///
+/// Tests pass a [`db::MemoryStore`] here to run every route without SurrealDB.
+fn build(
+    figment: Figment,
+    store: db::Store,
+    blobs: storage::Blobs,
+    storage: storage::Config,
+) -> Rocket<Build> {
+    // Rocket's own limits would reject uploads before `max_size` is checked
+    let figment = figment
+        .merge(("limits.file", storage.max_size))
+        .merge(("limits.data-form", storage.max_size + FORM_OVERHEAD));
+
+    rocket::custom(figment)
         .mount(
             "/",
             routes![
@@ -32,17 +105,61 @@ async fn rocket() -> _ {
                 register_page,
                 chat::home_page,
                 chat::group_page,
+                chat::invite_page,
                 chat::group::get,
                 chat::group::create,
+                chat::group::direct,
                 chat::group::member,
+                chat::group::role,
+                chat::group::update,
+                chat::group::upload_icon,
+                chat::group::remove_icon,
+                chat::group::leave,
+                chat::group::transfer,
+                chat::group::delete,
+                chat::group::restore,
+                chat::invite::create,
+                chat::invite::get,
+                chat::invite::revoke,
+                chat::invite::info,
+                chat::invite::accept,
+                chat::live::socket,
+                chat::live::events,
                 chat::message::get,
                 chat::message::send,
+                chat::message::edit,
+                chat::message::delete,
+                chat::message::revisions,
+                chat::message::react,
+                chat::message::unreact,
+                chat::message::thread,
+                chat::pin::get,
+                chat::pin::pin,
+                chat::pin::unpin,
+                chat::mention::get,
+                chat::mention::read,
+                chat::read::mark,
+                chat::read::seen_by,
+                chat::attachment::upload,
+                chat::attachment::download,
+                chat::search::all,
+                chat::search::in_group,
+                chat::typing::start,
+                chat::typing::get,
                 style,
                 user::login_req,
-                user::register_req
+                user::register_req,
+                user::upload_avatar,
+                user::remove_avatar,
+                user::avatar
             ],
         )
-        .manage(db)
+        .manage(store)
+        .manage(blobs)
+        .manage(storage)
+        .manage(hub::Hub::default())
+        .manage(typing::Typing::default())
+        .attach(purge::fairing())
 }
 
 #[get("/")]
@@ -76,3 +193,715 @@ fn register_page() -> (ContentType, &'static [u8]) {
 fn style() -> (ContentType, &'static [u8]) {
     (ContentType::CSS, include_bytes!("../../content/style.css"))
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Status},
+        local::asynchronous::Client,
+        serde::json::{serde_json::json, Value},
+    };
+
+    use super::*;
+    use crate::hub::{Event, Hub};
+
+    /// A client for the whole app on top of a [`db::MemoryStore`]
+    async fn client() -> Client {
+        client_with(|_| ()).await
+    }
+
+    async fn client_with(configure: impl FnOnce(&mut storage::Config)) -> Client {
+        let mut storage = storage::Config {
+            path: std::env::temp_dir().join(format!("chatter-test-{}", crypto::generate_token())),
+            ..storage::Config::default()
+        };
+        configure(&mut storage);
+        let blobs = storage::LocalStorage::new(&storage.path).unwrap();
+        let rocket = build(
+            rocket::Config::figment(),
+            Arc::new(db::MemoryStore::new()),
+            Arc::new(blobs),
+            storage,
+        );
+
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    /// Another client on the same stores as `client`, with its own cookies
+    async fn second_client(client: &Client) -> Client {
+        let rocket = client.rocket();
+        let rocket = build(
+            rocket::Config::figment(),
+            rocket.state::<db::Store>().unwrap().clone(),
+            rocket.state::<storage::Blobs>().unwrap().clone(),
+            rocket.state::<storage::Config>().unwrap().clone(),
+        );
+
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    async fn register(client: &Client, username: &str) {
+        let response = client
+            .post("/register")
+            .header(ContentType::JSON)
+            .body(
+                json!({
+                    "username": username,
+                    "email": format!("{username}@example.com"),
+                    "password": "password",
+                })
+                .to_string(),
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    /// Creates a group owned by the logged in user, returns its id
+    async fn new_group(client: &Client) -> String {
+        let group: Value = client
+            .post("/chat/create")
+            .header(ContentType::JSON)
+            .body(json!({ "name": "general" }).to_string())
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+
+        group["id"].as_str().unwrap().to_string()
+    }
+
+    async fn add_member(client: &Client, group: &str, username: &str) {
+        let response = client
+            .post(format!("/chat/{group}/member"))
+            .header(ContentType::JSON)
+            .body(json!({ "id": username, "is_remove": false }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    /// Sends `text` to `group`, returns the id of the message
+    async fn send(client: &Client, group: &str, text: &str) -> String {
+        let message: Value = client
+            .post(format!("/chat/{group}/send"))
+            .header(ContentType::JSON)
+            .body(json!({ "text": text }).to_string())
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+
+        message["id"].as_str().unwrap().to_string()
+    }
+
+    async fn edit(client: &Client, group: &str, id: &str, text: &str) -> Status {
+        client
+            .patch(format!("/chat/{group}/message/{id}"))
+            .header(ContentType::JSON)
+            .body(json!({ "text": text }).to_string())
+            .dispatch()
+            .await
+            .status()
+    }
+
+    /// Creates an invite to `group` from `invite`, returns its code
+    async fn create_invite(client: &Client, group: &str, invite: Value) -> String {
+        let invite: Value = client
+            .post(format!("/chat/{group}/invite"))
+            .header(ContentType::JSON)
+            .body(invite.to_string())
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+
+        invite["code"].as_str().unwrap().to_string()
+    }
+
+    async fn accept_invite(client: &Client, code: &str) -> Status {
+        client
+            .post(format!("/invite/{code}"))
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn login() {
+        let client = client().await;
+        register(&client, "alice").await;
+        client.cookies().remove("session");
+
+        let login = |password: &str| {
+            client
+                .post("/login")
+                .header(ContentType::JSON)
+                .body(json!({ "email": "alice@example.com", "password": password }).to_string())
+        };
+
+        let response = login("wrong").dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        assert!(response.cookies().get("session").is_none());
+
+        let response = login("password").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response.cookies().get("session").is_some());
+
+        let response = client.get("/chat/groups/10/0").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn create_group() {
+        let client = client().await;
+        let response = client
+            .post("/chat/create")
+            .header(ContentType::JSON)
+            .body(json!({ "name": "general" }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        register(&client, "alice").await;
+        let response = client
+            .post("/chat/create")
+            .header(ContentType::JSON)
+            .body(json!({ "name": "  general  " }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let group: Value = response.into_json().await.unwrap();
+        assert_eq!(group["name"], "general");
+        assert_eq!(group["role"], "owner");
+
+        let groups: Value = client
+            .get("/chat/groups/10/0")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(groups.as_array().unwrap().len(), 1);
+        assert_eq!(groups[0]["id"], group["id"]);
+    }
+
+    #[rocket::async_test]
+    async fn send_and_get_messages() {
+        let client = client().await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+        let group = group.as_str();
+
+        for text in ["first", "**second**"] {
+            let response = client
+                .post(format!("/chat/{group}/send"))
+                .header(ContentType::JSON)
+                .body(json!({ "text": text }).to_string())
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        let page: Value = client
+            .get(format!("/chat/{group}/messages?count=10"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let messages = page["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["text"], "**second**");
+        assert_eq!(messages[0]["html"], "<p><strong>second</strong></p>\n");
+        assert_eq!(messages[1]["author"], "alice");
+
+        // Only members can read the group
+        client.cookies().remove("session");
+        register(&client, "bob").await;
+        let response = client
+            .get(format!("/chat/{group}/messages?count=10"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn add_member_twice() {
+        let client = client().await;
+        register(&client, "bob").await;
+        client.cookies().remove("session");
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+        let group = group.as_str();
+
+        let add = || {
+            client
+                .post(format!("/chat/{group}/member"))
+                .header(ContentType::JSON)
+                .body(json!({ "id": "bob", "is_remove": false }).to_string())
+        };
+        assert_eq!(add().dispatch().await.status(), Status::Ok);
+        assert_eq!(add().dispatch().await.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn mentions_of_removed_members_are_hidden() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&alice, "alice").await;
+        let group = new_group(&alice).await;
+
+        let change_bob = |is_remove: bool| {
+            alice
+                .post(format!("/chat/{group}/member"))
+                .header(ContentType::JSON)
+                .body(json!({ "id": "bob", "is_remove": is_remove }).to_string())
+        };
+        assert_eq!(change_bob(false).dispatch().await.status(), Status::Ok);
+        let response = alice
+            .post(format!("/chat/{group}/send"))
+            .header(ContentType::JSON)
+            .body(json!({ "text": "hi @bob" }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let inbox = || async {
+            let page: Value = bob
+                .get("/mentions")
+                .dispatch()
+                .await
+                .into_json()
+                .await
+                .unwrap();
+            page["mentions"].as_array().unwrap().clone()
+        };
+        let mentions = inbox().await;
+        assert_eq!(mentions.len(), 1);
+        let mention = mentions[0]["id"].as_str().unwrap().to_string();
+
+        assert_eq!(change_bob(true).dispatch().await.status(), Status::Ok);
+        assert!(inbox().await.is_empty());
+        let response = bob
+            .post(format!("/mentions/{mention}/read"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn both_users_have_the_same_rights_in_direct_messages() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&alice, "alice").await;
+
+        let direct: Value = alice
+            .post("/chat/direct")
+            .header(ContentType::JSON)
+            .body(json!({ "id": "bob" }).to_string())
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let group = direct["id"].as_str().unwrap();
+
+        // Each user pins the other's message
+        for (client, other, text) in [(&alice, &bob, "from alice"), (&bob, &alice, "from bob")] {
+            let id = send(client, group, text).await;
+            let response = other
+                .put(format!("/chat/{group}/message/{id}/pin"))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok, "{text}");
+        }
+
+        // Neither user can delete or edit the other's messages
+        for (client, other) in [(&alice, &bob), (&bob, &alice)] {
+            let id = send(client, group, "mine").await;
+            let response = other
+                .delete(format!("/chat/{group}/message/{id}"))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Unauthorized);
+            assert_eq!(edit(other, group, &id, "yours").await, Status::Unauthorized);
+        }
+
+        let response = bob
+            .patch(format!("/chat/{group}"))
+            .header(ContentType::JSON)
+            .body(json!({ "name": "renamed" }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn only_raster_images_are_shown_inline() {
+        let client =
+            client_with(|storage| storage.allowed_types = vec!["image/*".to_string()]).await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+
+        for (mime, disposition) in [
+            ("image/png", "inline"),
+            ("image/svg+xml", "attachment"),
+            ("IMAGE/GIF", "inline"),
+        ] {
+            let body = format!(
+                "--boundary\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"picture\"\r\n\
+                Content-Type: {mime}\r\n\r\n\
+                <svg xmlns=\"http://www.w3.org/2000/svg\"><script>alert(1)</script></svg>\r\n\
+                --boundary--\r\n"
+            );
+            let attachment: Value = client
+                .post(format!("/chat/{group}/attachment"))
+                .header(
+                    ContentType::new("multipart", "form-data")
+                        .with_params(("boundary", "boundary")),
+                )
+                .body(body)
+                .dispatch()
+                .await
+                .into_json()
+                .await
+                .unwrap();
+            let id = attachment["id"].as_str().unwrap();
+
+            let response = client
+                .get(format!("/chat/{group}/attachment/{id}"))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            let headers = response.headers();
+            let served = headers.get_one("Content-Disposition").unwrap();
+            assert!(served.starts_with(disposition), "{mime}: {served}");
+            assert_eq!(headers.get_one("X-Content-Type-Options"), Some("nosniff"));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn replacing_a_group_icon_deletes_the_old_one() {
+        let client = client().await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(100, 50)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let mut body = b"--boundary\r\n\
+            Content-Disposition: form-data; name=\"picture\"; filename=\"icon.png\"\r\n\
+            Content-Type: image/png\r\n\r\n"
+            .to_vec();
+        body.extend_from_slice(&png);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        let mut icons = Vec::new();
+        for _ in 0..2 {
+            let group: Value = client
+                .post(format!("/chat/{group}/icon"))
+                .header(
+                    ContentType::new("multipart", "form-data")
+                        .with_params(("boundary", "boundary")),
+                )
+                .body(body.clone())
+                .dispatch()
+                .await
+                .into_json()
+                .await
+                .unwrap();
+            icons.push(group["icon"].as_str().unwrap().to_string());
+        }
+
+        let status = |url: String| {
+            let client = &client;
+            async move { client.get(url).dispatch().await.status() }
+        };
+        assert_eq!(status(icons[0].clone()).await, Status::NotFound);
+        assert_eq!(status(icons[1].clone()).await, Status::Ok);
+        let response = client
+            .delete(format!("/chat/{group}/icon"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(status(icons[1].clone()).await, Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn edits_keep_the_previous_texts() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&alice, "alice").await;
+        let group = new_group(&alice).await;
+        add_member(&alice, &group, "bob").await;
+
+        let id = send(&bob, &group, "helo").await;
+        assert_eq!(edit(&bob, &group, &id, "hello").await, Status::Ok);
+        // The owner may edit messages of other members, members may not
+        assert_eq!(edit(&alice, &group, &id, "hello!").await, Status::Ok);
+        let own = send(&alice, &group, "hi").await;
+        assert_eq!(edit(&bob, &group, &own, "bye").await, Status::Unauthorized);
+
+        let revisions: Value = bob
+            .get(format!("/chat/{group}/message/{id}/revisions"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let texts: Vec<&str> = revisions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|revision| revision["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, ["helo", "hello"]);
+
+        let page: Value = bob
+            .get(format!("/chat/{group}/messages?count=10"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let message = &page["messages"][1];
+        assert_eq!(message["text"], "hello!");
+        assert!(message["edited"].is_i64());
+    }
+
+    #[rocket::async_test]
+    async fn deleted_messages_leave_a_tombstone() {
+        let client = client().await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+        let id = send(&client, &group, "oops").await;
+        assert_eq!(edit(&client, &group, &id, "oops!").await, Status::Ok);
+
+        let delete = || client.delete(format!("/chat/{group}/message/{id}"));
+        assert_eq!(delete().dispatch().await.status(), Status::Ok);
+        assert_eq!(delete().dispatch().await.status(), Status::BadRequest);
+        assert_eq!(
+            edit(&client, &group, &id, "again").await,
+            Status::BadRequest
+        );
+
+        let page: Value = client
+            .get(format!("/chat/{group}/messages?count=10"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        let message = &page["messages"][0];
+        assert_eq!(message["id"], id.as_str());
+        assert_eq!(message["text"], "");
+        assert!(message["deleted"].is_i64());
+
+        let revisions: Value = client
+            .get(format!("/chat/{group}/message/{id}/revisions"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert!(revisions.as_array().unwrap().is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn edits_are_sent_without_the_editors_reactions() {
+        let client = client().await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+        let id = send(&client, &group, "hello").await;
+        let response = client
+            .put(format!("/chat/{group}/message/{id}/reaction/%F0%9F%91%8D"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let hub = client.rocket().state::<Hub>().unwrap();
+        let mut events = hub.subscribe(&db::RecordId::from(("group", group.as_str())));
+        let response = client
+            .patch(format!("/chat/{group}/message/{id}"))
+            .header(ContentType::JSON)
+            .body(json!({ "text": "hello!" }).to_string())
+            .dispatch()
+            .await;
+        let edited: Value = response.into_json().await.unwrap();
+        assert_eq!(edited["reactions"][0]["me"], true);
+
+        let Ok(Event::MessageUpdated(message)) = events.try_recv() else {
+            panic!("the edit wasn't published");
+        };
+        let sent = json!(message);
+        assert_eq!(sent["reactions"][0]["count"], 1);
+        assert!(sent["reactions"][0].get("me").is_none());
+    }
+
+    #[rocket::async_test]
+    async fn invites_stop_working_once_expired_used_up_or_revoked() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        let carol = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&carol, "carol").await;
+        register(&alice, "alice").await;
+        let group = new_group(&alice).await;
+
+        let once = create_invite(&alice, &group, json!({ "max_uses": 1 })).await;
+        assert_eq!(accept_invite(&bob, &once).await, Status::Ok);
+        assert_eq!(accept_invite(&carol, &once).await, Status::BadRequest);
+
+        let revoked = create_invite(&alice, &group, json!({})).await;
+        let response = alice
+            .delete(format!("/chat/{group}/invite/{revoked}"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(accept_invite(&carol, &revoked).await, Status::BadRequest);
+
+        // Invites can't be created with an expiry in the past
+        let response = alice
+            .post(format!("/chat/{group}/invite"))
+            .header(ContentType::JSON)
+            .body(json!({ "expires": 1 }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let store = alice.rocket().state::<db::Store>().unwrap();
+        store
+            .create_invite(db::CreateInvite {
+                code: "expired".to_string(),
+                group: db::RecordId::from(("group", group.as_str())),
+                creator: db::RecordId::from(("user", "alice")),
+                created: 0,
+                expires: Some(1),
+                max_uses: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(accept_invite(&carol, "expired").await, Status::BadRequest);
+
+        let response = carol
+            .get(format!("/chat/{group}/messages?count=10"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[rocket::async_test]
+    async fn members_accepting_an_invite_dont_use_it_up() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&alice, "alice").await;
+        let group = new_group(&alice).await;
+
+        let invites = || async {
+            let invites: Value = alice
+                .get(format!("/chat/{group}/invites"))
+                .dispatch()
+                .await
+                .into_json()
+                .await
+                .unwrap();
+            invites.as_array().unwrap().clone()
+        };
+        let once = create_invite(&alice, &group, json!({ "max_uses": 1 })).await;
+        assert_eq!(accept_invite(&alice, &once).await, Status::Ok);
+        assert_eq!(invites().await[0]["uses"], 0);
+
+        assert_eq!(accept_invite(&bob, &once).await, Status::Ok);
+        assert!(invites().await.is_empty());
+        // Accepting again as a member still works once the invite is used up
+        assert_eq!(accept_invite(&bob, &once).await, Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn owners_transfer_groups_before_leaving() {
+        let alice = client().await;
+        let bob = second_client(&alice).await;
+        register(&bob, "bob").await;
+        register(&alice, "alice").await;
+        let group = new_group(&alice).await;
+        add_member(&alice, &group, "bob").await;
+
+        async fn leave(client: &Client, group: &str) -> Status {
+            client
+                .post(format!("/chat/{group}/leave"))
+                .dispatch()
+                .await
+                .status()
+        }
+        async fn transfer(client: &Client, group: &str, to: &str) -> Status {
+            client
+                .put(format!("/chat/{group}/owner"))
+                .header(ContentType::JSON)
+                .body(json!({ "id": to }).to_string())
+                .dispatch()
+                .await
+                .status()
+        }
+
+        assert_eq!(leave(&alice, &group).await, Status::BadRequest);
+        assert_eq!(transfer(&bob, &group, "bob").await, Status::Unauthorized);
+        assert_eq!(transfer(&alice, &group, "bob").await, Status::Ok);
+
+        let groups: Value = bob
+            .get("/chat/groups/10/0")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert_eq!(groups[0]["role"], "owner");
+
+        assert_eq!(leave(&alice, &group).await, Status::Ok);
+        assert_eq!(leave(&bob, &group).await, Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    async fn deleted_groups_can_be_restored_until_they_are_purged() {
+        let client = client().await;
+        register(&client, "alice").await;
+        let group = new_group(&client).await;
+        send(&client, &group, "hello").await;
+
+        let status = |url: String| {
+            let client = &client;
+            async move { client.get(url).dispatch().await.status() }
+        };
+        let messages = format!("/chat/{group}/messages?count=10");
+        let response = client.delete(format!("/chat/{group}")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(status(messages.clone()).await, Status::BadRequest);
+        let groups: Value = client
+            .get("/chat/groups/10/0")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .unwrap();
+        assert!(groups.as_array().unwrap().is_empty());
+
+        let restore = || client.post(format!("/chat/{group}/restore"));
+        assert_eq!(restore().dispatch().await.status(), Status::Ok);
+        assert_eq!(status(messages).await, Status::Ok);
+
+        // Past the retention period the group can't be restored anymore
+        let store = client.rocket().state::<db::Store>().unwrap();
+        let id = db::RecordId::from(("group", group.as_str()));
+        let deleted = chrono::Utc::now().timestamp_millis() - purge::RETENTION - 1;
+        store.delete_group(id, deleted).await.unwrap().unwrap();
+        assert_eq!(restore().dispatch().await.status(), Status::BadRequest);
+    }
+}
@@ -0,0 +1,123 @@
+//! Renders the Markdown subset supported in messages to sanitized HTML
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use ammonia::UrlRelative;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// Everything the renderer may produce, anything else is removed
+///
+/// Only `href` is allowed as an attribute, so event handlers and inline
+/// styles can't make it through, and links are limited to absolute web and
+/// mail URLs.
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::empty();
+    builder
+        .add_tags(["p", "br", "strong", "em", "code", "pre", "a"])
+        .generic_attributes(HashSet::new())
+        .tag_attributes(HashMap::from([("a", HashSet::from(["href"]))]))
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .clean_content_tags(HashSet::from(["script", "style"]));
+    builder
+});
+
+/// Renders `text` to HTML which is safe to show with `innerHTML`
+///
+/// Bold, italics, code spans, fenced code blocks and links are supported. Raw
+/// HTML in the text is shown as text, images are turned into links and line
+/// breaks are kept like they were typed.
+pub fn render(text: &str) -> String {
+    let events = Parser::new(text).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::SoftBreak => Event::HardBreak,
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }),
+        Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+        event => event,
+    });
+
+    let mut html = String::with_capacity(text.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events);
+
+    SANITIZER.clean(&html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn scripts_are_shown_as_text() {
+        assert_eq!(
+            render("<script>alert(1)</script>"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+        assert_eq!(
+            render("<div>\n<script>alert(1)</script>\n</div>"),
+            "&lt;div&gt;\n&lt;script&gt;alert(1)&lt;/script&gt;\n&lt;/div&gt;"
+        );
+    }
+
+    #[test]
+    fn javascript_links_lose_their_href() {
+        for text in [
+            "[x](javascript:alert(1))",
+            "![x](javascript:alert(1))",
+            "[x](data:text/html,<script>alert(1)</script>)",
+            "[x](/relative)",
+        ] {
+            assert_eq!(
+                render(text),
+                "<p><a rel=\"noopener noreferrer nofollow\">x</a></p>\n",
+                "{text}"
+            );
+        }
+        assert_eq!(
+            render("[x](https://example.com)"),
+            "<p><a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">x</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn event_handlers_are_stripped() {
+        assert_eq!(
+            render("<img src=x onerror=alert(1)>"),
+            "&lt;img src=x onerror=alert(1)&gt;"
+        );
+        assert_eq!(
+            render("a <b onclick=\"alert(1)\">b</b>"),
+            "<p>a &lt;b onclick=\"alert(1)\"&gt;b&lt;/b&gt;</p>\n"
+        );
+    }
+
+    #[test]
+    fn raw_html_is_shown_as_text() {
+        for text in [
+            "<a href=\"javascript:alert(1)\">x</a>",
+            "<iframe src=\"https://example.com\"></iframe>",
+            "<style>body { display: none }</style>",
+            "<p style=\"color: red\">red</p>",
+        ] {
+            let html = render(text);
+            assert!(
+                !html.replace("<p>", "").replace("</p>", "").contains('<'),
+                "{text}: {html}"
+            );
+        }
+    }
+}
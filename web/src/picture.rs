@@ -0,0 +1,143 @@
+//! Square profile pictures, decoded and re-encoded so nothing but the pixels
+//! of an upload is ever served back
+
+use std::io::Cursor;
+
+use image::{imageops::FilterType, DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use rocket::{
+    fs::TempFile,
+    http::{ContentType, Header},
+    tokio::io::AsyncReadExt,
+};
+
+use crate::storage;
+
+/// Edge lengths of the variants produced for every picture, in pixels
+pub const SIZES: [u32; 2] = [64, 256];
+/// Larger images are rejected before they are decoded
+const MAX_DIMENSION: u32 = 8192;
+const MAX_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// Decodes an uploaded picture and returns a PNG for each of [`SIZES`]
+///
+/// Non-square pictures are cropped around their center. Re-encoding drops
+/// EXIF and every other kind of metadata, the EXIF orientation is applied
+/// beforehand so photos stay upright.
+pub fn square_variants(data: &[u8]) -> image::ImageResult<Vec<(u32, Vec<u8>)>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut picture = DynamicImage::from_decoder(decoder)?;
+    picture.apply_orientation(orientation);
+    let picture = DynamicImage::ImageRgba8(picture.to_rgba8());
+
+    SIZES
+        .iter()
+        .map(|&size| {
+            let mut png = Vec::new();
+            picture
+                .resize_to_fill(size, size, FilterType::Lanczos3)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            Ok((size, png))
+        })
+        .collect()
+}
+
+/// Why an uploaded picture couldn't be stored
+pub enum UploadError {
+    /// Larger than the configured `max_size`
+    TooLarge,
+    /// Not an image, or not one that can be decoded
+    Unsupported,
+    /// Reading, processing or storing the picture failed, the cause is logged
+    Internal(&'static str),
+}
+
+impl UploadError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            UploadError::TooLarge => "The picture is too large.",
+            UploadError::Unsupported => "That isn't a supported image.",
+            UploadError::Internal(message) => message,
+        }
+    }
+}
+
+/// Stores every variant of an uploaded picture, returns the key of the picture
+pub async fn store(
+    blobs: &storage::Blobs,
+    upload: &TempFile<'_>,
+    max_size: u64,
+) -> Result<String, UploadError> {
+    if upload.len() > max_size {
+        return Err(UploadError::TooLarge);
+    }
+
+    let mut data = Vec::with_capacity(upload.len() as usize);
+    let read = match upload.open().await {
+        Ok(mut reader) => reader.read_to_end(&mut data).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = read {
+        error!("Upload: {e:?}");
+        return Err(UploadError::Internal("Failed to read the picture."));
+    }
+
+    // Decoding and resizing is CPU bound, keep it off the async workers
+    let variants = match rocket::tokio::task::spawn_blocking(move || square_variants(&data)).await {
+        Ok(Ok(variants)) => variants,
+        Ok(Err(_)) => return Err(UploadError::Unsupported),
+        Err(e) => {
+            error!("Picture: {e:?}");
+            return Err(UploadError::Internal("Failed to process the picture."));
+        }
+    };
+
+    let picture = crypto::generate_token();
+    for (size, png) in variants {
+        if let Err(e) = blobs.put(&blob_key(&picture, size), &png).await {
+            error!("Storage: {e:?}");
+            return Err(UploadError::Internal("Failed to store the picture."));
+        }
+    }
+
+    Ok(picture)
+}
+
+/// Deletes every variant of a picture, failures are only logged
+pub async fn delete(blobs: &storage::Blobs, picture: &str) {
+    for size in SIZES {
+        if let Err(e) = blobs.delete(&blob_key(picture, size)).await {
+            error!("Storage: {e:?}");
+        }
+    }
+}
+
+/// The blob key of one variant of a picture
+pub fn blob_key(picture: &str, size: u32) -> String {
+    format!("{picture}-{size}")
+}
+
+/// A variant as produced by [`square_variants`], cacheable forever since
+/// every upload gets a new key
+#[derive(Responder)]
+pub struct Picture {
+    inner: (ContentType, Vec<u8>),
+    cache_control: Header<'static>,
+}
+
+impl Picture {
+    pub fn png(data: Vec<u8>) -> Self {
+        Self {
+            inner: (ContentType::PNG, data),
+            cache_control: Header::new("Cache-Control", "public, max-age=31536000, immutable"),
+        }
+    }
+}
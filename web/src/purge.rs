@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use rocket::{fairing::AdHoc, tokio};
+
+use crate::{picture, storage};
+
+/// How long a deleted group can be restored before it's purged, in milliseconds
+pub const RETENTION: i64 = 30 * 24 * 60 * 60 * 1000;
+/// How long an uploaded attachment is kept without being sent, in milliseconds
+pub const UNSENT_RETENTION: i64 = 24 * 60 * 60 * 1000;
+/// How often deleted groups and unsent attachments are checked for being
+/// past their retention
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Removes deleted groups for good once they are past [`RETENTION`], and
+/// attachments which haven't been sent within [`UNSENT_RETENTION`]
+///
+/// Runs in the background from liftoff until the server shuts down.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Purge deleted groups and unsent attachments", |rocket| {
+        Box::pin(async move {
+            let (Some(database), Some(blobs)) = (
+                rocket.state::<db::Store>().cloned(),
+                rocket.state::<storage::Blobs>().cloned(),
+            ) else {
+                return;
+            };
+
+            let mut shutdown = rocket.shutdown();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => purge(&database, &blobs).await,
+                        _ = &mut shutdown => break,
+                    }
+                }
+            });
+        })
+    })
+}
+
+async fn purge(database: &db::Store, blobs: &storage::Blobs) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut attachments = Vec::new();
+    match database.purge_groups(now - RETENTION).await {
+        Ok(purged) => {
+            attachments.extend(purged.attachments);
+            for icon in purged.icons {
+                picture::delete(blobs, &icon).await;
+            }
+        }
+        Err(e) => error!("Database: {e:?}"),
+    }
+    match database
+        .remove_unsent_attachments(now - UNSENT_RETENTION)
+        .await
+    {
+        Ok(unsent) => attachments.extend(unsent),
+        Err(e) => error!("Database: {e:?}"),
+    }
+
+    for attachment in attachments {
+        if let Err(e) = blobs.delete(&attachment.id.key().to_string()).await {
+            error!("Storage: {e:?}");
+        }
+    }
+}
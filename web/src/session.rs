@@ -7,7 +7,7 @@ use rocket::{http::CookieJar, State};
 /// NOTE: when a database error occurs the error is printed to stdout
 pub async fn verify(
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
 ) -> Option<Option<db::Session>> {
     let Some(session) = cookies.get("session") else {
         return Some(None);
@@ -0,0 +1,125 @@
+use std::{io, path::PathBuf, sync::Arc};
+
+use rocket::tokio::fs;
+
+/// Limits and location of uploaded files, read from the `storage` table of Rocket's config
+///
+/// ```toml
+/// [default.storage]
+/// path = "/var/lib/chatter/uploads"
+/// max_size = 10485760
+/// allowed_types = ["image/png", "image/jpeg", "text/plain", "application/pdf"]
+/// ```
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct Config {
+    /// Directory of the [`LocalStorage`]
+    pub path: PathBuf,
+    /// Largest accepted upload, in bytes
+    pub max_size: u64,
+    /// MIME types that may be uploaded, `type/*` allows every subtype
+    ///
+    /// Only PNG, JPEG, GIF and WebP images are shown inline, everything else
+    /// is served as a download.
+    pub allowed_types: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("uploads"),
+            max_size: 10 * 1024 * 1024,
+            // SVG is left out on purpose, it can carry scripts
+            allowed_types: [
+                "image/png",
+                "image/jpeg",
+                "image/gif",
+                "image/webp",
+                "text/plain",
+                "application/pdf",
+                "application/json",
+                "application/zip",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+impl Config {
+    pub fn allows(&self, mime: &str) -> bool {
+        self.allowed_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(top) => mime
+                    .split_once('/')
+                    .is_some_and(|(mime_top, _)| mime_top.eq_ignore_ascii_case(top)),
+                None => allowed.eq_ignore_ascii_case(mime),
+            })
+    }
+}
+
+/// Keeps the content of uploaded files, addressed by a key chosen by the caller
+#[rocket::async_trait]
+pub trait BlobStore: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Returns `None` when there is no blob stored under `key`
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Shared handle to whichever [`BlobStore`] the server was started with
+pub type Blobs = Arc<dyn BlobStore>;
+
+/// A [`BlobStore`] which keeps every blob as a file in one directory
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    /// Keys become file names, so they are limited to characters which can't
+    /// escape `root`
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        let valid = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid blob key `{key}`"),
+            ));
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[rocket::async_trait]
+impl BlobStore for LocalStorage {
+    async fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        fs::write(self.path(key)?, data).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long a typing signal lasts, clients repeat it while the user keeps typing
+pub const TTL: Duration = Duration::from_secs(5);
+
+/// Who is currently composing a message in which group
+///
+/// Signals are only kept in memory and expire after [`TTL`], nothing about
+/// them is ever written to the database.
+#[derive(Default)]
+pub struct Typing {
+    /// Group `RecordId` to username to when the signal expires
+    groups: Mutex<HashMap<String, HashMap<String, Instant>>>,
+}
+
+impl Typing {
+    /// Shows `user` as typing in `group` for the next [`TTL`]
+    pub fn start(&self, group: &db::RecordId, user: &db::RecordId) {
+        let now = Instant::now();
+        let mut groups = self.groups.lock().unwrap();
+        // Signals of groups nobody asks about anymore would pile up otherwise
+        groups.retain(|_, users| {
+            users.retain(|_, expires| *expires > now);
+            !users.is_empty()
+        });
+
+        groups
+            .entry(group.to_string())
+            .or_default()
+            .insert(user.key().to_string(), now + TTL);
+    }
+
+    /// Stops showing `user` as typing, e.g. once their message has been sent
+    pub fn stop(&self, group: &db::RecordId, user: &db::RecordId) {
+        let mut groups = self.groups.lock().unwrap();
+        let key = group.to_string();
+        let Some(users) = groups.get_mut(&key) else {
+            return;
+        };
+
+        users.remove(&user.key().to_string());
+        if users.is_empty() {
+            groups.remove(&key);
+        }
+    }
+
+    /// Returns the usernames of everyone typing in `group`, sorted
+    pub fn typing(&self, group: &db::RecordId) -> Vec<String> {
+        let now = Instant::now();
+        let groups = self.groups.lock().unwrap();
+        let Some(users) = groups.get(&group.to_string()) else {
+            return Vec::new();
+        };
+
+        let mut typing: Vec<String> = users
+            .iter()
+            .filter(|(_, expires)| **expires > now)
+            .map(|(user, _)| user.clone())
+            .collect();
+        typing.sort();
+
+        typing
+    }
+}
@@ -1,12 +1,16 @@
 #![allow(private_interfaces)]
 
 use rocket::{
+    form::Form,
+    fs::TempFile,
     http::{Cookie, CookieJar, Status},
     serde::json::Json,
     State,
 };
 use zeroize::Zeroize;
 
+use crate::{picture, session, storage};
+
 #[derive(serde::Deserialize)]
 struct LoginCredentials<'a> {
     email: &'a str,
@@ -20,11 +24,21 @@ struct RegisterCredentials<'a> {
     password: String,
 }
 
+#[derive(FromForm)]
+struct AvatarUpload<'r> {
+    picture: TempFile<'r>,
+}
+
+/// Where the `size` variant of a profile picture is served
+pub fn avatar_url(avatar: &str, size: u32) -> String {
+    format!("/avatar/{avatar}/{size}")
+}
+
 #[post("/login", format = "json", data = "<credentials>")]
 pub async fn login_req(
     mut credentials: Json<LoginCredentials<'_>>,
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
 ) -> (Status, &'static str) {
     let user = match database.get_user_by_email(credentials.email).await {
         Ok(Some(user)) => user,
@@ -74,7 +88,7 @@ pub async fn login_req(
 pub async fn register_req(
     mut credentials: Json<RegisterCredentials<'_>>,
     cookies: &CookieJar<'_>,
-    database: &State<db::DBConnection>,
+    database: &State<db::Store>,
 ) -> (Status, &'static str) {
     // Hash and Zeroize the password
     let hashed_password = crypto::hash_password(credentials.password.as_bytes());
@@ -139,3 +153,99 @@ pub async fn register_req(
     cookies.add(Cookie::new("session", token));
     (Status::Ok, "")
 }
+
+/// Replaces the profile picture of the logged in user
+#[post("/user/avatar", data = "<upload>")]
+pub async fn upload_avatar(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    config: &State<storage::Config>,
+    upload: Form<AvatarUpload<'_>>,
+) -> (Status, &'static str) {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return (Status::Unauthorized, ""),
+        None => return (Status::InternalServerError, "Internal Database Error"),
+    };
+
+    let avatar = match picture::store(blobs, &upload.picture, config.max_size).await {
+        Ok(avatar) => avatar,
+        Err(e @ picture::UploadError::TooLarge) => return (Status::PayloadTooLarge, e.message()),
+        Err(e @ picture::UploadError::Unsupported) => return (Status::BadRequest, e.message()),
+        Err(e) => return (Status::InternalServerError, e.message()),
+    };
+
+    set_avatar(database, blobs, session.user, Some(avatar)).await
+}
+
+/// Removes the profile picture of the logged in user
+#[delete("/user/avatar")]
+pub async fn remove_avatar(
+    cookies: &CookieJar<'_>,
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+) -> (Status, &'static str) {
+    let session = match session::verify(cookies, database).await {
+        Some(Some(session)) => session,
+        Some(None) => return (Status::Unauthorized, ""),
+        None => return (Status::InternalServerError, "Internal Database Error"),
+    };
+
+    set_avatar(database, blobs, session.user, None).await
+}
+
+/// Points `user` at a new profile picture and deletes the previous one
+async fn set_avatar(
+    database: &State<db::Store>,
+    blobs: &State<storage::Blobs>,
+    user: db::RecordId,
+    avatar: Option<String>,
+) -> (Status, &'static str) {
+    let previous = match database.get_user(&user.key().to_string()).await {
+        Ok(Some(user)) => user.avatar,
+        Ok(None) => return (Status::Unauthorized, ""),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return (Status::InternalServerError, "Internal Database Error");
+        }
+    };
+
+    match database.set_avatar(user, avatar).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return (Status::Unauthorized, ""),
+        Err(e) => {
+            error!("Database: {e:?}");
+            return (Status::InternalServerError, "Internal Database Error");
+        }
+    }
+
+    if let Some(previous) = previous {
+        picture::delete(blobs, &previous).await;
+    }
+
+    (Status::Ok, "")
+}
+
+/// Serves a variant of a profile picture, see [`picture::SIZES`]
+#[get("/avatar/<avatar>/<size>")]
+pub async fn avatar(
+    blobs: &State<storage::Blobs>,
+    avatar: &str,
+    size: u32,
+) -> Result<picture::Picture, Status> {
+    if !picture::SIZES.contains(&size) {
+        return Err(Status::NotFound);
+    }
+
+    match blobs.get(&picture::blob_key(avatar, size)).await {
+        Ok(Some(png)) => Ok(picture::Picture::png(png)),
+        Ok(None) => Err(Status::NotFound),
+        // Keys which can't be stored are never handed out
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => Err(Status::NotFound),
+        Err(e) => {
+            error!("Storage: {e:?}");
+            Err(Status::InternalServerError)
+        }
+    }
+}